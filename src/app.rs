@@ -1,91 +1,300 @@
+use std::mem;
+use std::ops::RangeInclusive;
+
+use auto_graph::compatibility::{Compatibility, Quantity, Verdict};
+use auto_graph::diagnostics::{completions, identifier_start};
+use auto_graph::fill::{Fill, FillKind};
+use auto_graph::fit::FIT_PARAMETERS;
+use auto_graph::format::{format_measure, NumberFormat};
+use auto_graph::number::DecimalSeparator;
+use auto_graph::outliers::OutlierTest;
+use auto_graph::project::{
+    is_valid_sheet_name, link_sheets, load_sheet_names, save_sheet_names, sheet_identifier,
+    sheet_prefix,
+};
+use auto_graph::sheet::{self, Calibration, Constant, Sheet, UncertaintyModel, Value};
+use auto_graph::stats::{normal_density, BinWidth, Histogram};
 use eframe::Storage;
 use egui::{
-    plot::{BoxElem, BoxPlot, BoxSpread, Line, Plot, PlotPoints},
-    CentralPanel, Color32, Context, DragValue, RichText, SidePanel, Stroke, TextEdit,
+    plot::{
+        Bar, BarChart, BoxElem, BoxPlot, BoxSpread, Line, MarkerShape, Plot, PlotPoints, Points,
+    },
+    text::{CCursor, CCursorRange},
+    widgets::text_edit::TextEditState,
+    Button, CentralPanel, Color32, ComboBox, Context, CursorIcon, DragValue, Event, Id, Key,
+    KeyboardShortcut, Label, Modifiers, RichText, Sense, SidePanel, Stroke, TextEdit,
     TopBottomPanel, Visuals, Window,
 };
 use egui_extras::{Column, TableBuilder};
-use evalexpr::{
-    eval_number_with_context, ContextWithMutableVariables, EvalexprError, HashMapContext,
-};
 
-const SAMPLE_COUNT: isize = 1;
+use crate::history::History;
+
+/// Names proposed at once by the completion of expressions
+const COMPLETION_COUNT: usize = 8;
+
+/// Reads of other lines and columns, shown over the expression field
+const EXPRESSION_HELP: &str = "i is the line number, prev(x) and next(x) read x on the previous \
+                               and next lines, x(n) reads it on the line n, like x(i - 2)\n\
+                               mean(x), sum(x), min(x), max(x) and stddev(x) read the whole column";
+
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(
+    Modifiers {
+        shift: true,
+        ..Modifiers::COMMAND
+    },
+    Key::Z,
+);
+const REDO_ALTERNATIVE_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+
+/// Color of the lines flagged by the outlier test
+const OUTLIER_COLOR: Color32 = Color32::from_rgb(255, 127, 0);
 
 const DARK_THEME_KEY: &str = "dark_them";
 const VERTICAL_BOX_PLOT_KEY: &str = "vertical_box_plot";
 const FULL_BOX_PLOT_KEY: &str = "full_box_plot";
-const LINEAR_REGRESSION_KEY: &str = "linear_regression";
-
-const COLUMN_COUNT_KEY: &str = "column_count";
-const LINE_COUNT_KEY: &str = "line_count";
-const COLUMN_NAME_KEY: &str = "column_name";
-const COLUMN_EXPRESSION_KEY: &str = "column_expression";
-const COLUMN_PRECISION_KEY: &str = "column_precision";
-const GRID_VALUE_KEY: &str = "grid_value";
-const GRID_UNCERTAINTY_KEY: &str = "grid_uncertainty";
+const COLUMN_ORDER_KEY: &str = "column_order";
 
 #[derive(Clone)]
 enum PopupStatus {
     None,
     ColumnSettings(usize),
     GlobalSettings,
+    Constants,
+    Functions,
+    Statistics,
+    Outliers,
+    Compatibility,
 }
 
-#[derive(Clone)]
-struct Value {
-    raw_value: String,
-    raw_uncertainty: String,
-    value: f64,
-    uncertainty: f64,
+/// Position of a cell of the table, `uncertainty` is true for the Δ cell of a column
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct CellRef {
+    line: usize,
+    column: usize,
+    uncertainty: bool,
+}
+
+/// Change of the columns requested from the table header, applied after it is drawn
+#[derive(Clone, Copy)]
+enum ColumnAction {
+    Duplicate(usize),
+    /// Moves a column in the display order, positions are indices in `column_order`
+    Move {
+        from: usize,
+        to: usize,
+    },
+}
+
+/// Change of the lines of the grid requested from the table, applied after it is drawn
+#[derive(Clone, Copy)]
+enum LineAction {
+    InsertAbove(usize),
+    InsertBelow(usize),
+    Duplicate(usize),
+    Remove(usize),
+    Move { from: usize, to: usize },
+}
+
+/// Change of the tabs requested from the tab bar, applied after it is drawn
+#[derive(Clone, Copy)]
+enum TabAction {
+    Select(usize),
+    Add,
+    Remove(usize),
+}
+
+/// Sheet of the project with the state of the app that belongs to it, the selected sheet is
+/// swapped with the fields of `App` while it is shown
+struct Tab {
+    name: String,
+    sheet: Sheet,
+    column_order: Vec<usize>,
+    history: History<Snapshot>,
+    vertical_box_plot: bool,
+    full_box_plot: bool,
+    histogram_column: Option<usize>,
 }
 
-impl Value {
-    const fn new() -> Self {
+impl Tab {
+    /// Sheet with the default y and x columns
+    fn new(name: String) -> Self {
+        let mut sheet = Sheet::new();
+        sheet.add_column("y".to_owned());
+        sheet.add_column("x".to_owned());
+
         Self {
-            raw_value: String::new(),
-            raw_uncertainty: String::new(),
-            value: f64::NAN,
-            uncertainty: 0.,
+            name,
+            sheet,
+            column_order: vec![0, 1],
+            history: History::new(Snapshot::default()),
+            vertical_box_plot: true,
+            full_box_plot: false,
+            histogram_column: None,
+        }
+    }
+
+    /// Reads a sheet saved by `App::save`, `get` gives the text stored for the keys of the sheet
+    fn load(name: String, get: &dyn Fn(&str) -> Option<String>) -> Self {
+        let mut tab = Self {
+            sheet: Sheet::load(get),
+            ..Self::new(name)
+        };
+
+        if let Some(vertical_box_plot_str) = get(VERTICAL_BOX_PLOT_KEY) {
+            if let Ok(vertical_box_plot) = vertical_box_plot_str.parse::<bool>() {
+                tab.vertical_box_plot = vertical_box_plot;
+            }
+        }
+
+        if let Some(full_box_plot_str) = get(FULL_BOX_PLOT_KEY) {
+            if let Ok(full_box_plot) = full_box_plot_str.parse::<bool>() {
+                tab.full_box_plot = full_box_plot;
+            }
+        }
+
+        if tab.sheet.columns.is_empty() {
+            tab.sheet.add_column("y".to_owned());
+            tab.sheet.add_column("x".to_owned());
+        }
+
+        tab.column_order = (0..tab.sheet.columns.len()).collect();
+
+        if let Some(column_order_str) = get(COLUMN_ORDER_KEY) {
+            let column_order: Vec<usize> = column_order_str
+                .split(',')
+                .filter_map(|x| x.parse::<usize>().ok())
+                .collect();
+
+            // Only a permutation of the columns is a valid order
+            let mut sorted = column_order.clone();
+            sorted.sort_unstable();
+
+            if sorted.into_iter().eq(0..tab.sheet.columns.len()) {
+                tab.column_order = column_order;
+            }
         }
+
+        tab
+    }
+
+    fn save(&self, set: &mut dyn FnMut(&str, String)) {
+        set(VERTICAL_BOX_PLOT_KEY, self.vertical_box_plot.to_string());
+        set(FULL_BOX_PLOT_KEY, self.full_box_plot.to_string());
+        self.sheet.save(set);
+
+        set(
+            COLUMN_ORDER_KEY,
+            self.column_order
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>()
+                .join(","),
+        );
     }
 }
 
 pub struct App {
-    grid: Vec<Vec<Value>>,
-    columns: Vec<ColumnSettings>,
+    /// Sheets of the project, the fields of the selected one hold placeholders while its data
+    /// is in `App`
+    tabs: Vec<Tab>,
+    current_tab: usize,
+    /// Tab whose name is being edited, with its name before the edit
+    renamed_tab: Option<(usize, String)>,
+    sheet: Sheet,
+    /// Order in which the columns are shown, expressions still use the order of `columns`
+    column_order: Vec<usize>,
+    /// File the library is loaded from and saved to
+    #[cfg(not(target_arch = "wasm32"))]
+    library_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    library_message: String,
     popup_status: PopupStatus,
     dark_theme: bool,
     vertical_box_plot: bool,
     full_box_plot: bool,
-    linear_regression: bool,
-}
-
-pub struct ColumnSettings {
-    name: String,
-    expression: String,
-    precision: usize,
+    /// Column shown as a histogram in the graph panel instead of the fit
+    histogram_column: Option<usize>,
+    bin_width: BinWidth,
+    /// Whether the normal distribution with the mean and deviation of the column is drawn over
+    /// the histogram
+    normal_overlay: bool,
+    /// Column tested by the outlier window, the fit residuals if there is none
+    outlier_column: Option<usize>,
+    outlier_test: OutlierTest,
+    /// Lines flagged by the outlier test while its window is open, highlighted in the table and
+    /// the plot
+    outlier_lines: Vec<usize>,
+    /// Values compared by the compatibility window
+    compared: [Quantity; 2],
+    /// Normalized differences below which the values are compatible and above which they are
+    /// incompatible
+    compatibility_thresholds: [f64; 2],
+    history: History<Snapshot>,
+    /// Line whose index is being dragged to reorder the lines
+    dragged_line: Option<usize>,
+    /// Position in `column_order` of the column whose header is being dragged
+    dragged_column: Option<usize>,
+    /// Cell where the text cursor is, used as the destination of pasted blocks
+    focused_cell: Option<CellRef>,
+    /// Corners of the selected rectangle of cells, the first one is where the selection started
+    selection: Option<(CellRef, CellRef)>,
+    /// Cell that receives the focus when it is drawn, after a navigation key
+    pending_focus: Option<CellRef>,
+    /// Series used by the fill action of the column settings
+    fill: Fill,
 }
 
-impl ColumnSettings {
-    const fn new(name: String) -> Self {
-        Self {
-            name,
-            expression: String::new(),
-            precision: 3,
-        }
-    }
+/// Everything that can be undone, computed values are rebuilt from it
+#[derive(Clone, PartialEq, Default)]
+struct Snapshot {
+    columns: Vec<sheet::Column>,
+    column_order: Vec<usize>,
+    constants: Vec<Constant>,
+    function_library: String,
+    /// Raw value and raw uncertainty of the cells, without the empty lines at the end
+    cells: Vec<Vec<(String, String)>>,
+    /// Lines left out of the fit, until the last excluded one
+    excluded_lines: Vec<bool>,
+    vertical_box_plot: bool,
+    full_box_plot: bool,
+    linear_regression: bool,
+    decimal_separator: DecimalSeparator,
+    fit_name: String,
 }
 
 impl App {
     pub fn new(cc: &eframe::CreationContext) -> Self {
         let mut app = Self {
-            grid: Vec::new(),
-            columns: Vec::new(),
+            tabs: Vec::new(),
+            current_tab: 0,
+            renamed_tab: None,
+            sheet: Sheet::new(),
+            column_order: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            library_path: "functions.txt".to_owned(),
+            #[cfg(not(target_arch = "wasm32"))]
+            library_message: String::new(),
             popup_status: PopupStatus::None,
             dark_theme: false,
             vertical_box_plot: true,
             full_box_plot: false,
-            linear_regression: true,
+            histogram_column: None,
+            bin_width: BinWidth::Sturges,
+            normal_overlay: true,
+            outlier_column: None,
+            outlier_test: OutlierTest::Chauvenet,
+            outlier_lines: Vec::new(),
+            compared: [Quantity::FitParameter(0), Quantity::Constant(0)],
+            compatibility_thresholds: [2., 3.],
+            history: History::new(Snapshot::default()),
+            dragged_line: None,
+            dragged_column: None,
+            focused_cell: None,
+            selection: None,
+            pending_focus: None,
+            fill: Fill::default(),
         };
 
         if let Some(storage) = cc.storage {
@@ -95,378 +304,2171 @@ impl App {
                 }
             }
 
-            if let Some(vertical_box_plot_str) = storage.get_string(VERTICAL_BOX_PLOT_KEY) {
-                if let Ok(vertical_box_plot) = vertical_box_plot_str.parse::<bool>() {
-                    app.vertical_box_plot = vertical_box_plot;
+            let names = load_sheet_names(&|key| storage.get_string(key));
+
+            app.tabs = names
+                .into_iter()
+                .enumerate()
+                .map(|(tab_n, name)| {
+                    let prefix = sheet_prefix(tab_n);
+                    Tab::load(name, &|key| storage.get_string(&format!("{prefix}{key}")))
+                })
+                .collect();
+        }
+
+        if app.tabs.is_empty() {
+            app.tabs.push(Tab::new("sheet1".to_owned()));
+        }
+
+        for tab_n in 0..app.tabs.len() {
+            app.swap_tab(tab_n);
+            app.history = History::new(app.snapshot());
+            app.swap_tab(tab_n);
+        }
+
+        app.swap_tab(app.current_tab);
+        app.link_sheets();
+
+        app
+    }
+
+    /// Exchanges the sheet shown by the app with the one stored in a tab, swapping the selected
+    /// tab twice leaves everything in place
+    fn swap_tab(&mut self, tab_n: usize) {
+        let tab = &mut self.tabs[tab_n];
+
+        mem::swap(&mut self.sheet, &mut tab.sheet);
+        mem::swap(&mut self.column_order, &mut tab.column_order);
+        mem::swap(&mut self.history, &mut tab.history);
+        mem::swap(&mut self.vertical_box_plot, &mut tab.vertical_box_plot);
+        mem::swap(&mut self.full_box_plot, &mut tab.full_box_plot);
+        mem::swap(&mut self.histogram_column, &mut tab.histogram_column);
+    }
+
+    /// Gives every sheet the constants and fit parameters of the others
+    fn link_sheets(&mut self) {
+        self.swap_tab(self.current_tab);
+
+        let names: Vec<String> = self.tabs.iter().map(|tab| tab.name.clone()).collect();
+        let mut sheets: Vec<&mut Sheet> = self.tabs.iter_mut().map(|tab| &mut tab.sheet).collect();
+        link_sheets(&mut sheets, &names);
+
+        self.swap_tab(self.current_tab);
+    }
+
+    /// Keeps the expressions and calibrations reading the values of a sheet working after it is
+    /// renamed
+    fn rename_sheet_references(&mut self, old_sheet: &str, new_sheet: &str) {
+        self.swap_tab(self.current_tab);
+
+        for tab in &mut self.tabs {
+            tab.sheet.rename_sheet_references(old_sheet, new_sheet);
+        }
+
+        self.swap_tab(self.current_tab);
+    }
+
+    /// Shows another tab
+    fn select_tab(&mut self, ctx: &Context, tab_n: usize) {
+        self.swap_tab(self.current_tab);
+        self.current_tab = tab_n;
+        self.swap_tab(self.current_tab);
+
+        self.forget_sheet_state(ctx);
+    }
+
+    /// Drops the state tied to the cells of the sheet that was shown, and gives the newly shown
+    /// sheet the current values of the others
+    fn forget_sheet_state(&mut self, ctx: &Context) {
+        surrender_focus(ctx);
+        self.focused_cell = None;
+        self.selection = None;
+        self.pending_focus = None;
+        self.dragged_line = None;
+        self.dragged_column = None;
+        self.outlier_column = None;
+        self.outlier_lines.clear();
+
+        if let PopupStatus::ColumnSettings(_) = self.popup_status {
+            self.popup_status = PopupStatus::None;
+        }
+
+        self.link_sheets();
+    }
+
+    /// Name `sheet{n}` that no tab uses yet
+    fn new_tab_name(&self) -> String {
+        (1..=self.tabs.len() + 1)
+            .map(|n| format!("sheet{n}"))
+            .find(|name| self.tabs.iter().all(|tab| tab.name != *name))
+            .unwrap_or_default()
+    }
+
+    fn apply_tab_action(&mut self, ctx: &Context, action: TabAction) {
+        match action {
+            TabAction::Select(tab_n) => {
+                if tab_n != self.current_tab {
+                    self.select_tab(ctx, tab_n);
                 }
             }
+            TabAction::Add => {
+                self.tabs.push(Tab::new(self.new_tab_name()));
+                self.select_tab(ctx, self.tabs.len() - 1);
+                self.history = History::new(self.snapshot());
+            }
+            TabAction::Remove(tab_n) => {
+                self.swap_tab(self.current_tab);
+                self.tabs.remove(tab_n);
 
-            if let Some(full_box_plot_str) = storage.get_string(FULL_BOX_PLOT_KEY) {
-                if let Ok(full_box_plot) = full_box_plot_str.parse::<bool>() {
-                    app.full_box_plot = full_box_plot;
+                if self.current_tab > tab_n || self.current_tab == self.tabs.len() {
+                    self.current_tab -= 1;
                 }
+
+                self.swap_tab(self.current_tab);
+                self.forget_sheet_state(ctx);
             }
+        }
+    }
 
-            if let Some(linear_regression_str) = storage.get_string(LINEAR_REGRESSION_KEY) {
-                if let Ok(linear_regression) = linear_regression_str.parse::<bool>() {
-                    app.linear_regression = linear_regression;
-                }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            columns: self.sheet.columns.clone(),
+            column_order: self.column_order.clone(),
+            constants: self.sheet.constants.clone(),
+            function_library: self.sheet.function_library.clone(),
+            cells: self.sheet.grid[..self.sheet.filled_line_count()]
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|cell| (cell.raw_value.clone(), cell.raw_uncertainty.clone()))
+                        .collect()
+                })
+                .collect(),
+            excluded_lines: self.excluded_lines(),
+            vertical_box_plot: self.vertical_box_plot,
+            full_box_plot: self.full_box_plot,
+            linear_regression: self.sheet.linear_regression,
+            decimal_separator: self.sheet.decimal_separator,
+            fit_name: self.sheet.fit_name.clone(),
+        }
+    }
+
+    /// Exclusion of the lines until the last excluded one, so that trailing `false` entries
+    /// don't make an undo step
+    fn excluded_lines(&self) -> Vec<bool> {
+        let count = self
+            .sheet
+            .excluded_lines
+            .iter()
+            .rposition(|excluded| *excluded)
+            .map_or(0, |line_n| line_n + 1);
+
+        self.sheet.excluded_lines[..count].to_vec()
+    }
+
+    /// Compares without building a snapshot since this is done every frame
+    fn matches_snapshot(&self, snapshot: &Snapshot) -> bool {
+        self.sheet.columns == snapshot.columns
+            && self.column_order == snapshot.column_order
+            && self.sheet.constants == snapshot.constants
+            && self.sheet.function_library == snapshot.function_library
+            && self.sheet.filled_line_count() == snapshot.cells.len()
+            && self
+                .sheet
+                .grid
+                .iter()
+                .zip(&snapshot.cells)
+                .all(|(line, cells)| {
+                    line.iter().zip(cells).all(|(cell, (value, uncertainty))| {
+                        cell.raw_value == *value && cell.raw_uncertainty == *uncertainty
+                    })
+                })
+            && (0..self
+                .sheet
+                .excluded_lines
+                .len()
+                .max(snapshot.excluded_lines.len()))
+                .all(|line_n| {
+                    self.sheet.line_excluded(line_n)
+                        == snapshot
+                            .excluded_lines
+                            .get(line_n)
+                            .copied()
+                            .unwrap_or(false)
+                })
+            && self.vertical_box_plot == snapshot.vertical_box_plot
+            && self.full_box_plot == snapshot.full_box_plot
+            && self.sheet.linear_regression == snapshot.linear_regression
+            && self.sheet.decimal_separator == snapshot.decimal_separator
+            && self.sheet.fit_name == snapshot.fit_name
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.sheet.columns = snapshot.columns;
+        self.column_order = snapshot.column_order;
+        self.sheet.constants = snapshot.constants;
+        self.sheet.function_library = snapshot.function_library;
+        self.sheet.grid = snapshot
+            .cells
+            .into_iter()
+            .map(|cells| {
+                cells
+                    .into_iter()
+                    .map(|(raw_value, raw_uncertainty)| Value {
+                        raw_value,
+                        raw_uncertainty,
+                        ..Value::new()
+                    })
+                    .collect()
+            })
+            .collect();
+        self.sheet.excluded_lines = snapshot.excluded_lines;
+        self.vertical_box_plot = snapshot.vertical_box_plot;
+        self.full_box_plot = snapshot.full_box_plot;
+        self.sheet.linear_regression = snapshot.linear_regression;
+        self.sheet.decimal_separator = snapshot.decimal_separator;
+        self.sheet.fit_name = snapshot.fit_name;
+
+        if let PopupStatus::ColumnSettings(column_index) = self.popup_status {
+            if column_index >= self.sheet.columns.len() {
+                self.popup_status = PopupStatus::None;
             }
+        }
 
-            if let Some(column_count_str) = storage.get_string(COLUMN_COUNT_KEY) {
-                if let Ok(column_count) = column_count_str.parse::<usize>() {
-                    if column_count >= 2 {
-                        for column_n in 0..column_count {
-                            let column_name = storage
-                                .get_string(&format!("{COLUMN_NAME_KEY}_{column_n}"))
-                                .unwrap();
+        for column in [&mut self.histogram_column, &mut self.outlier_column] {
+            if column.is_some_and(|column_index| column_index >= self.sheet.columns.len()) {
+                *column = None;
+            }
+        }
 
-                            let column_expression = storage
-                                .get_string(&format!("{COLUMN_EXPRESSION_KEY}_{column_n}"))
-                                .unwrap();
+        self.sheet.ensure_empty_line();
+        self.sheet.compute_and_parse_all();
 
-                            let column_precision = storage
-                                .get_string(&format!("{COLUMN_PRECISION_KEY}_{column_n}"))
-                                .unwrap()
-                                .parse::<usize>()
-                                .unwrap();
+        // The other sheets read the restored constants and fit, and calibrations read theirs
+        self.link_sheets();
+    }
 
-                            app.add_column(column_name);
-                            let last = app.columns.last_mut().unwrap();
-                            last.expression = column_expression;
-                            last.precision = column_precision;
-                        }
+    /// Records the changes made since the last recorded state, changes are grouped until the
+    /// edited field loses focus or the mouse is released
+    fn record_history(&mut self, ctx: &Context) {
+        let editing =
+            ctx.memory(|memory| memory.focus().is_some()) || ctx.input(|i| i.pointer.any_down());
 
-                        if let Some(line_count_str) = storage.get_string(LINE_COUNT_KEY) {
-                            if let Ok(line_count) = line_count_str.parse::<usize>() {
-                                for column_n in 0..column_count {
-                                    for line_n in 0..line_count {
-                                        let value = storage
-                                            .get_string(&format!(
-                                                "{GRID_VALUE_KEY}_{line_n}_{column_n}"
-                                            ))
-                                            .unwrap();
-
-                                        let uncertainty = storage
-                                            .get_string(&format!(
-                                                "{GRID_UNCERTAINTY_KEY}_{line_n}_{column_n}"
-                                            ))
-                                            .unwrap();
-
-                                        app.add_line();
-
-                                        app.grid[line_n][column_n].raw_value = value;
-                                        app.grid[line_n][column_n].raw_uncertainty = uncertainty;
-                                    }
-                                }
-                            }
-                        }
-                    }
+        if !editing && !self.matches_snapshot(self.history.current()) {
+            self.history.record(self.snapshot());
+        }
+    }
+
+    fn undo(&mut self, ctx: &Context) {
+        if !self.matches_snapshot(self.history.current()) {
+            self.history.record(self.snapshot());
+        }
+
+        if let Some(snapshot) = self.history.undo() {
+            surrender_focus(ctx);
+            self.restore(snapshot);
+        }
+    }
+
+    fn redo(&mut self, ctx: &Context) {
+        // Redoing after an unrecorded change would lose it
+        if !self.matches_snapshot(self.history.current()) {
+            return;
+        }
+
+        if let Some(snapshot) = self.history.redo() {
+            surrender_focus(ctx);
+            self.restore(snapshot);
+        }
+    }
+
+    fn add_column(&mut self, name: String) {
+        self.column_order.push(self.sheet.columns.len());
+        self.sheet.add_column(name);
+    }
+
+    fn remove_column(&mut self, index: usize) {
+        self.sheet.remove_column(index);
+        self.column_order.retain(|x| *x != index);
+
+        for x in &mut self.column_order {
+            if *x > index {
+                *x -= 1;
+            }
+        }
+
+        for column in [&mut self.histogram_column, &mut self.outlier_column] {
+            *column = match *column {
+                Some(column_index) if column_index == index => None,
+                Some(column_index) if column_index > index => Some(column_index - 1),
+                column => column,
+            };
+        }
+    }
+
+    /// Duplicates a column and shows the copy next to the original, the duplicate buttons are
+    /// disabled for the columns which can't be copied
+    fn duplicate_column(&mut self, index: usize) {
+        let Ok(copy_index) = self.sheet.duplicate_column(index) else {
+            return;
+        };
+
+        for x in &mut self.column_order {
+            if *x >= copy_index {
+                *x += 1;
+            }
+        }
+
+        let position = self.column_order.iter().position(|x| *x == index).unwrap();
+        self.column_order.insert(position + 1, copy_index);
+
+        if let PopupStatus::ColumnSettings(column_index) = &mut self.popup_status {
+            if *column_index >= copy_index {
+                *column_index += 1;
+            }
+        }
+
+        for column_index in [&mut self.histogram_column, &mut self.outlier_column]
+            .into_iter()
+            .flatten()
+        {
+            if *column_index >= copy_index {
+                *column_index += 1;
+            }
+        }
+    }
+
+    fn apply_column_action(&mut self, action: ColumnAction) {
+        match action {
+            ColumnAction::Duplicate(column_index) => self.duplicate_column(column_index),
+            ColumnAction::Move { from, to } => {
+                let column_index = self.column_order.remove(from);
+                self.column_order.insert(to, column_index);
+            }
+        }
+    }
+
+    fn apply_line_action(&mut self, action: LineAction) {
+        let empty_line = vec![Value::new(); self.sheet.columns.len()];
+
+        match action {
+            LineAction::InsertAbove(line_n) => self.sheet.insert_line(line_n, empty_line, false),
+            LineAction::InsertBelow(line_n) => {
+                self.sheet.insert_line(line_n + 1, empty_line, false);
+            }
+            LineAction::Duplicate(line_n) => {
+                let line = self.sheet.grid[line_n].clone();
+                let excluded = self.sheet.line_excluded(line_n);
+                self.sheet.insert_line(line_n + 1, line, excluded);
+            }
+            LineAction::Remove(line_n) => {
+                self.sheet.remove_line(line_n);
+            }
+            LineAction::Move { from, to } => {
+                let (line, excluded) = self.sheet.remove_line(from);
+                self.sheet.insert_line(to, line, excluded);
+            }
+        }
+
+        self.sheet.ensure_empty_line();
+        self.sheet.compute_and_parse_all();
+    }
+
+    /// Columns of the table in display order, with `true` for uncertainty columns
+    fn visible_cells(&self) -> Vec<(usize, bool)> {
+        self.column_order
+            .iter()
+            .filter(|x| !self.sheet.columns[**x].hidden)
+            .flat_map(|&x| {
+                std::iter::once((x, false))
+                    .chain((!self.sheet.columns[x].uncertainty_hidden).then_some((x, true)))
+            })
+            .collect()
+    }
+
+    /// Updates the focused cell and the selection, shift click extends the selection
+    fn track_cell_focus(&mut self, response: &egui::Response, cell: CellRef) {
+        if response.has_focus() {
+            self.focused_cell = Some(cell);
+        }
+
+        if response.clicked() || response.gained_focus() {
+            let extend = response.ctx.input(|i| i.modifiers.shift);
+            self.select(cell, cell, extend);
+        }
+    }
+
+    /// Gives the focus to an editable cell if a navigation key moved to it, then handles the
+    /// navigation keys pressed in it : Enter goes down, Tab goes across value and Δ cells and the
+    /// arrows move when the cursor was at the boundary of the text
+    fn navigate_cells(
+        &mut self,
+        ui: &egui::Ui,
+        input: &egui::Response,
+        cell: CellRef,
+        cursor: Option<CCursorRange>,
+        text_length: usize,
+    ) {
+        if self.pending_focus == Some(cell) {
+            input.request_focus();
+            input.scroll_to_me(None);
+            self.pending_focus = None;
+        }
+
+        if !input.has_focus() && !input.lost_focus() {
+            return;
+        }
+
+        let (at_start, at_end) = cursor.map_or((true, true), |cursor| {
+            let (start, end) = (cursor.primary.index, cursor.secondary.index);
+            (
+                start == 0 && end == 0,
+                start == text_length && end == text_length,
+            )
+        });
+
+        let target = ui.input(|i| {
+            let shift = i.modifiers.shift;
+
+            if i.key_pressed(Key::Enter) && input.lost_focus() {
+                self.vertical_neighbour(cell, shift)
+            } else if !input.has_focus() {
+                None
+            } else if i.key_pressed(Key::Tab) {
+                self.horizontal_neighbour(cell, !shift, true)
+            } else if i.key_pressed(Key::ArrowUp) {
+                self.vertical_neighbour(cell, true)
+            } else if i.key_pressed(Key::ArrowDown) {
+                self.vertical_neighbour(cell, false)
+            } else if i.key_pressed(Key::ArrowLeft) && at_start {
+                self.horizontal_neighbour(cell, false, false)
+            } else if i.key_pressed(Key::ArrowRight) && at_end {
+                self.horizontal_neighbour(cell, true, false)
+            } else {
+                None
+            }
+        });
+
+        if let Some(target) = target {
+            self.pending_focus = Some(target);
+            self.selection = Some((target, target));
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Same cell on the line above or below, the empty line at the end can be reached
+    fn vertical_neighbour(&self, cell: CellRef, up: bool) -> Option<CellRef> {
+        let line = if up {
+            cell.line.checked_sub(1)?
+        } else {
+            Some(cell.line + 1).filter(|line| *line < self.sheet.grid.len())?
+        };
+
+        Some(CellRef { line, ..cell })
+    }
+
+    /// Next or previous editable cell in display order, wrapping to the neighbouring lines if
+    /// `wrap` is set
+    fn horizontal_neighbour(&self, cell: CellRef, forward: bool, wrap: bool) -> Option<CellRef> {
+        let editable: Vec<(usize, bool)> = self
+            .visible_cells()
+            .into_iter()
+            .filter(|(column, _)| self.sheet.columns[*column].expression.is_empty())
+            .collect();
+
+        let position = editable.iter().position(|&(column, uncertainty)| {
+            column == cell.column && uncertainty == cell.uncertainty
+        })?;
+
+        let (line, position) = if forward {
+            if position + 1 < editable.len() {
+                (cell.line, position + 1)
+            } else if wrap && cell.line + 1 < self.sheet.grid.len() {
+                (cell.line + 1, 0)
+            } else {
+                return None;
+            }
+        } else if position > 0 {
+            (cell.line, position - 1)
+        } else if wrap && cell.line > 0 {
+            (cell.line - 1, editable.len() - 1)
+        } else {
+            return None;
+        };
+
+        Some(CellRef {
+            line,
+            column: editable[position].0,
+            uncertainty: editable[position].1,
+        })
+    }
+
+    /// Selects the rectangle between two cells, or extends the current selection to `end`
+    const fn select(&mut self, start: CellRef, end: CellRef, extend: bool) {
+        self.selection = match self.selection {
+            Some((anchor, _)) if extend => Some((anchor, end)),
+            _ => Some((start, end)),
+        };
+    }
+
+    /// Lines and positions in `cells` covered by the selection
+    fn selection_bounds(
+        &self,
+        cells: &[(usize, bool)],
+    ) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+        let (start, end) = self.selection?;
+
+        let position = |cell: CellRef| {
+            cells.iter().position(|&(column, uncertainty)| {
+                column == cell.column && uncertainty == cell.uncertainty
+            })
+        };
+
+        let (start_position, end_position) = (position(start)?, position(end)?);
+
+        Some((
+            start.line.min(end.line)..=start.line.max(end.line),
+            start_position.min(end_position)..=start_position.max(end_position),
+        ))
+    }
+
+    /// Selected cells as tab separated values
+    fn copy_selection(&self) -> Option<String> {
+        let cells = self.visible_cells();
+        let (lines, positions) = self.selection_bounds(&cells)?;
+
+        Some(
+            lines
+                .filter(|line| *line < self.sheet.grid.len())
+                .map(|line| {
+                    cells[positions.clone()]
+                        .iter()
+                        .map(|&(column, uncertainty)| {
+                            self.sheet.cell_text(line, column, uncertainty)
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\t")
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+
+    /// Fills the measured cells from tab and newline separated text, starting at the top left of
+    /// the selection, the grid grows to fit the pasted lines
+    fn paste_block(&mut self, text: &str) {
+        let cells = self.visible_cells();
+
+        let Some((first_line, first_position)) = self
+            .selection_bounds(&cells)
+            .map(|(lines, positions)| (*lines.start(), *positions.start()))
+            .or_else(|| {
+                let focused = self.focused_cell?;
+                let position = cells.iter().position(|&(column, uncertainty)| {
+                    column == focused.column && uncertainty == focused.uncertainty
+                })?;
+                Some((focused.line, position))
+            })
+        else {
+            return;
+        };
+
+        let block: Vec<&str> = text.trim_end_matches(['\r', '\n']).lines().collect();
+
+        for (i, text_line) in block.iter().enumerate() {
+            let line = first_line + i;
+
+            while self.sheet.grid.len() <= line {
+                self.sheet.add_line();
+            }
+
+            for (j, text_cell) in text_line.split('\t').enumerate() {
+                let Some(&(column, uncertainty)) = cells.get(first_position + j) else {
+                    break;
+                };
+
+                if !self.sheet.columns[column].expression.is_empty() {
+                    continue;
+                }
+
+                let cell = &mut self.sheet.grid[line][column];
+
+                if uncertainty {
+                    text_cell.trim().clone_into(&mut cell.raw_uncertainty);
+                } else {
+                    text_cell.trim().clone_into(&mut cell.raw_value);
                 }
             }
         }
 
-        app.compute_and_parse_all();
+        let width = block
+            .iter()
+            .map(|text_line| text_line.split('\t').count())
+            .max()
+            .unwrap_or(1);
+        let last_position = (first_position + width - 1).min(cells.len() - 1);
+
+        let corner = |line: usize, position: usize| CellRef {
+            line,
+            column: cells[position].0,
+            uncertainty: cells[position].1,
+        };
+
+        self.selection = Some((
+            corner(first_line, first_position),
+            corner(first_line + block.len().max(1) - 1, last_position),
+        ));
+
+        self.sheet.ensure_empty_line();
+        self.sheet.compute_and_parse_all();
+    }
+
+    fn show_value_cell(&mut self, ui: &mut egui::Ui, y: usize, x: usize) {
+        let cell = CellRef {
+            line: y,
+            column: x,
+            uncertainty: false,
+        };
+
+        if self.sheet.columns[x].expression.is_empty() {
+            let invalid = self.sheet.grid[y][x].value.is_nan();
+            let id = Id::new(cell);
+            let cursor = TextEditState::load(ui.ctx(), id).and_then(|state| state.ccursor_range());
+            let text_length = self.sheet.grid[y][x].raw_value.chars().count();
+
+            let mut text_edit = TextEdit::singleline(&mut self.sheet.grid[y][x].raw_value)
+                .id(id)
+                .lock_focus(true);
+
+            if invalid {
+                text_edit = text_edit.text_color(Color32::RED);
+            }
+
+            let mut input = ui.add(text_edit);
+
+            if invalid && !self.sheet.grid[y][x].raw_value.trim().is_empty() {
+                input = input.on_hover_ui(|ui| {
+                    ui.label(
+                        self.sheet
+                            .cell_error(cell.line, cell.column, cell.uncertainty),
+                    );
+                });
+            }
+
+            self.track_cell_focus(&input, cell);
+            self.navigate_cells(ui, &input, cell, cursor, text_length);
+
+            if input.lost_focus() {
+                trim_in_place(&mut self.sheet.grid[y][x].raw_value);
+            }
+
+            if input.changed() {
+                self.sheet.parse_cell(y, x);
+            }
+        } else if y != self.sheet.grid.len() - 1 {
+            let value = self.sheet.grid[y][x].value;
+            let (value_text, _) = self.sheet.columns[x].format_measure(
+                value,
+                self.sheet.grid[y][x].uncertainty,
+                self.sheet.decimal_separator,
+            );
+
+            let mut rich_text = RichText::new(value_text);
+
+            if value.is_nan() {
+                rich_text = rich_text.color(Color32::RED);
+            }
+
+            let mut label = ui.add(Label::new(rich_text).sense(Sense::click()));
+
+            if value.is_nan() {
+                label = label.on_hover_ui(|ui| {
+                    ui.label(
+                        self.sheet
+                            .cell_error(cell.line, cell.column, cell.uncertainty),
+                    );
+                });
+            }
+
+            self.track_cell_focus(&label, cell);
+        }
+    }
+
+    fn show_uncertainty_cell(&mut self, ui: &mut egui::Ui, y: usize, x: usize) {
+        let cell = CellRef {
+            line: y,
+            column: x,
+            uncertainty: true,
+        };
+
+        if self.sheet.columns[x].expression.is_empty() {
+            let value = self.sheet.grid[y][x].value;
+            let uncertainty = self.sheet.grid[y][x].uncertainty;
+            let invalid = uncertainty.is_nan();
+
+            // Shows the uncertainty given by the column model
+            let hint = (self.sheet.columns[x].uncertainty_model != UncertaintyModel::PerCell
+                && !value.is_nan())
+            .then(|| {
+                self.sheet.columns[x]
+                    .format_measure(value, uncertainty, self.sheet.decimal_separator)
+                    .1
+            });
+
+            let id = Id::new(cell);
+            let cursor = TextEditState::load(ui.ctx(), id).and_then(|state| state.ccursor_range());
+            let text_length = self.sheet.grid[y][x].raw_uncertainty.chars().count();
+
+            let mut text_edit = TextEdit::singleline(&mut self.sheet.grid[y][x].raw_uncertainty)
+                .id(id)
+                .lock_focus(true);
+
+            if let Some(hint) = hint {
+                text_edit = text_edit.hint_text(hint);
+            }
+
+            if invalid {
+                text_edit = text_edit.text_color(Color32::RED);
+            }
+
+            let mut input = ui.add(text_edit);
+
+            if invalid {
+                input = input.on_hover_ui(|ui| {
+                    ui.label(
+                        self.sheet
+                            .cell_error(cell.line, cell.column, cell.uncertainty),
+                    );
+                });
+            }
+
+            self.track_cell_focus(&input, cell);
+            self.navigate_cells(ui, &input, cell, cursor, text_length);
+
+            if input.lost_focus() {
+                trim_in_place(&mut self.sheet.grid[y][x].raw_uncertainty);
+            }
+
+            if input.changed() {
+                self.sheet.parse_cell(y, x);
+            }
+        } else if y != self.sheet.grid.len() - 1 {
+            let uncertainty = self.sheet.grid[y][x].uncertainty;
+            let (_, uncertainty_text) = self.sheet.columns[x].format_measure(
+                self.sheet.grid[y][x].value,
+                uncertainty,
+                self.sheet.decimal_separator,
+            );
+
+            let mut rich_text = RichText::new(uncertainty_text);
+            if uncertainty.is_nan() {
+                rich_text = rich_text.color(Color32::RED);
+            }
+
+            let mut label = ui.add(Label::new(rich_text).sense(Sense::click()));
+
+            if uncertainty.is_nan() {
+                label = label.on_hover_ui(|ui| {
+                    ui.label(
+                        self.sheet
+                            .cell_error(cell.line, cell.column, cell.uncertainty),
+                    );
+                });
+            }
+
+            self.track_cell_focus(&label, cell);
+        }
+    }
+
+    /// Column name with a context menu, dragging it moves the column
+    fn show_column_header(
+        &mut self,
+        ui: &mut egui::Ui,
+        column_index: usize,
+        uncertainty: bool,
+        column_action: &mut Option<ColumnAction>,
+    ) {
+        let rect = ui.max_rect();
+        let position = self
+            .column_order
+            .iter()
+            .position(|x| *x == column_index)
+            .unwrap();
+
+        let response = if uncertainty {
+            let title =
+                RichText::new(format!("Δ{}", self.sheet.columns[column_index].name)).heading();
+            ui.add(Label::new(title).sense(Sense::click_and_drag()))
+        } else {
+            ui.horizontal(|ui| {
+                let title = RichText::new(self.sheet.column_title(column_index)).heading();
+                let response = ui.add(Label::new(title).sense(Sense::click_and_drag()));
+
+                if ui.button("⚙".to_owned()).clicked() {
+                    self.popup_status = PopupStatus::ColumnSettings(column_index);
+                }
+
+                response
+            })
+            .inner
+        }
+        .on_hover_cursor(CursorIcon::Grab);
+
+        if response.drag_started() {
+            self.dragged_column = Some(position);
+        }
+
+        if response.clicked() && self.sheet.grid.len() > 1 {
+            let cell = |line| CellRef {
+                line,
+                column: column_index,
+                uncertainty,
+            };
+            let extend = ui.input(|i| i.modifiers.shift);
+            self.select(cell(0), cell(self.sheet.grid.len() - 2), extend);
+        }
+
+        if let Some(dragged_column) = self.dragged_column {
+            let hovered = ui
+                .ctx()
+                .pointer_interact_pos()
+                .is_some_and(|pointer| rect.x_range().contains(&pointer.x));
+
+            if hovered && dragged_column != position {
+                let x = if position < dragged_column {
+                    rect.left()
+                } else {
+                    rect.right()
+                };
+
+                ui.painter().vline(
+                    x,
+                    rect.y_range(),
+                    Stroke::new(2., ui.visuals().selection.bg_fill),
+                );
+
+                if ui.input(|i| i.pointer.any_released()) {
+                    *column_action = Some(ColumnAction::Move {
+                        from: dragged_column,
+                        to: position,
+                    });
+                }
+            }
+        }
+
+        response.context_menu(|ui| {
+            let duplication_error = self.sheet.duplication_error(column_index);
+
+            if ui
+                .add_enabled(duplication_error.is_none(), Button::new("Duplicate column"))
+                .on_disabled_hover_text(duplication_error.unwrap_or_default())
+                .clicked()
+            {
+                *column_action = Some(ColumnAction::Duplicate(column_index));
+                ui.close_menu();
+            }
+
+            if ui.button("Hide column").clicked() {
+                self.sheet.columns[column_index].hidden = true;
+                ui.close_menu();
+            }
+
+            if ui.button("Hide Δ column").clicked() {
+                self.sheet.columns[column_index].uncertainty_hidden = true;
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Line number with a context menu to edit lines, dragging it moves the line
+    fn show_line_index(
+        &mut self,
+        ui: &mut egui::Ui,
+        line_n: usize,
+        line_action: &mut Option<LineAction>,
+    ) {
+        let rect = ui.max_rect();
+
+        let excluded = self.sheet.line_excluded(line_n);
+        let mut text = RichText::new((line_n + 1).to_string());
+
+        if excluded {
+            text = text.strikethrough().weak();
+        }
+
+        if self.outlier_lines.contains(&line_n) {
+            text = text.color(OUTLIER_COLOR);
+        }
+
+        let response = ui
+            .add(Label::new(text).sense(Sense::click_and_drag()))
+            .on_hover_cursor(CursorIcon::Grab);
+
+        if response.drag_started() {
+            self.dragged_line = Some(line_n);
+        }
+
+        if response.clicked() {
+            let cells = self.visible_cells();
+
+            if let (Some(&first), Some(&last)) = (cells.first(), cells.last()) {
+                let cell = |(column, uncertainty)| CellRef {
+                    line: line_n,
+                    column,
+                    uncertainty,
+                };
+                let extend = ui.input(|i| i.modifiers.shift);
+                self.select(cell(first), cell(last), extend);
+            }
+        }
+
+        if let Some(dragged_line) = self.dragged_line {
+            let hovered = ui
+                .ctx()
+                .pointer_interact_pos()
+                .is_some_and(|pointer| rect.y_range().contains(&pointer.y));
+
+            if hovered && dragged_line != line_n {
+                let y = if line_n < dragged_line {
+                    rect.top()
+                } else {
+                    rect.bottom()
+                };
+
+                ui.painter().hline(
+                    rect.x_range(),
+                    y,
+                    Stroke::new(2., ui.visuals().selection.bg_fill),
+                );
+
+                if ui.input(|i| i.pointer.any_released()) {
+                    *line_action = Some(LineAction::Move {
+                        from: dragged_line,
+                        to: line_n,
+                    });
+                }
+            }
+        }
+
+        response.context_menu(|ui| {
+            for (action, text) in [
+                (LineAction::InsertAbove(line_n), "Insert line above"),
+                (LineAction::InsertBelow(line_n), "Insert line below"),
+                (LineAction::Duplicate(line_n), "Duplicate line"),
+                (LineAction::Remove(line_n), "Remove line"),
+            ] {
+                if ui.button(text).clicked() {
+                    *line_action = Some(action);
+                    ui.close_menu();
+                }
+            }
+
+            if ui
+                .button(if excluded {
+                    "Include in fit"
+                } else {
+                    "Exclude from fit"
+                })
+                .clicked()
+            {
+                self.sheet.set_line_excluded(line_n, !excluded);
+                self.sheet.compute_all();
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Histogram of a column with the bin width settings, replaces the fit plot
+    fn show_histogram(&mut self, ui: &mut egui::Ui, column_index: usize) {
+        let mut column_index = column_index;
+
+        egui::ComboBox::from_label("Column")
+            .selected_text(self.sheet.columns[column_index].name.clone())
+            .show_ui(ui, |ui| {
+                for &x in &self.column_order {
+                    ui.selectable_value(&mut column_index, x, self.sheet.columns[x].name.clone());
+                }
+            });
+
+        self.histogram_column = Some(column_index);
+
+        let values: Vec<f64> = self
+            .sheet
+            .grid
+            .iter()
+            .map(|line| line[column_index].value)
+            .collect();
+        let histogram = Histogram::new(&values, self.bin_width);
+
+        ui.horizontal(|ui| {
+            ui.label("Bin width");
+            ui.radio_value(&mut self.bin_width, BinWidth::Sturges, "Sturges");
+            ui.radio_value(
+                &mut self.bin_width,
+                BinWidth::FreedmanDiaconis,
+                "Freedman–Diaconis",
+            );
+
+            // The fixed width starts from the automatic one
+            if ui
+                .radio(matches!(self.bin_width, BinWidth::Fixed(_)), "Fixed")
+                .clicked()
+            {
+                self.bin_width = BinWidth::Fixed(histogram.width);
+            }
+
+            if let BinWidth::Fixed(width) = &mut self.bin_width {
+                ui.add(
+                    DragValue::new(width)
+                        .speed(histogram.width / 100.)
+                        .clamp_range(0.0..=f64::INFINITY),
+                );
+            }
+        });
+
+        let statistics = self.sheet.column_statistics(column_index);
+        let column = &self.sheet.columns[column_index];
+        let separator = self.sheet.decimal_separator;
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.normal_overlay, "Normal distribution");
+
+            if self.normal_overlay && statistics.standard_deviation > 0. {
+                let (mean, standard_error) =
+                    column.format_measure(statistics.mean, statistics.standard_error, separator);
+                let (deviation, _) =
+                    column.format_measure(statistics.standard_deviation, 0., separator);
+
+                ui.label(format!("Mean : {mean} ± {standard_error}, σ : {deviation}"));
+            }
+        });
+
+        let bars: Vec<Bar> = histogram
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| {
+                Bar::new(
+                    histogram.start + (bin as f64 + 0.5) * histogram.width,
+                    *count as f64,
+                )
+                .width(histogram.width)
+            })
+            .collect();
+
+        let chart = BarChart::new(bars).color(Color32::from_rgb(63, 127, 255));
+
+        // The density is scaled to the number of values per bin
+        let scale = statistics.count as f64 * histogram.width;
+        let end = histogram.start + histogram.counts.len() as f64 * histogram.width;
+        let (mean, deviation) = (statistics.mean, statistics.standard_deviation);
+        let curve = (self.normal_overlay && deviation > 0.).then(|| {
+            Line::new(PlotPoints::from_explicit_callback(
+                move |x| scale * normal_density(x, mean, deviation),
+                (mean - 4. * deviation).min(histogram.start)..(mean + 4. * deviation).max(end),
+                512,
+            ))
+            .width(2.)
+            .color(Color32::from_rgb(255, 63, 63))
+        });
+
+        let unit = self.sheet.column_unit_label(column_index);
+
+        Plot::new("histogram")
+            .x_axis_formatter(move |x, range| axis_label(x, range, separator, &unit))
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(chart);
+
+                if let Some(curve) = curve {
+                    plot_ui.line(curve);
+                }
+            });
+    }
+
+    fn show_constants(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("Constants")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut changed = false;
+                let mut removed = None;
+
+                egui::Grid::new("constants").striped(true).show(ui, |ui| {
+                    ui.label("Name");
+                    ui.label("Value");
+                    ui.label("Δ");
+                    ui.label("Unit");
+                    ui.end_row();
+
+                    for (constant_n, constant) in self.sheet.constants.iter_mut().enumerate() {
+                        let value_color = if constant.si_value.is_nan() {
+                            Color32::RED
+                        } else {
+                            ui.visuals().text_color()
+                        };
+                        let uncertainty_color = if constant.si_uncertainty.is_nan() {
+                            Color32::RED
+                        } else {
+                            ui.visuals().text_color()
+                        };
+
+                        for (text, width, color) in [
+                            (&mut constant.name, 40., ui.visuals().text_color()),
+                            (&mut constant.value, 100., value_color),
+                            (&mut constant.uncertainty, 60., uncertainty_color),
+                            (&mut constant.unit, 60., ui.visuals().text_color()),
+                        ] {
+                            changed |= ui
+                                .add(
+                                    TextEdit::singleline(text)
+                                        .desired_width(width)
+                                        .text_color(color),
+                                )
+                                .changed();
+                        }
+
+                        if ui.button("🗑").clicked() {
+                            removed = Some(constant_n);
+                        }
+
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("add constant").clicked() {
+                        self.sheet.constants.push(Constant::new("k", "1", "", ""));
+                        changed = true;
+                    }
+
+                    for preset in Constant::presets() {
+                        let exists = self.sheet.constants.iter().any(|c| c.name == preset.name);
+
+                        if ui
+                            .add_enabled(!exists, Button::new(format!("add {}", preset.name)))
+                            .clicked()
+                        {
+                            self.sheet.constants.push(preset);
+                            changed = true;
+                        }
+                    }
+                });
+
+                if let Some(constant_n) = removed {
+                    self.sheet.constants.remove(constant_n);
+                    changed = true;
+                }
+
+                if changed {
+                    self.sheet.compute_all();
+                }
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    fn show_statistics(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("Statistics")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("statistics").striped(true).show(ui, |ui| {
+                    for header in [
+                        "Column",
+                        "n",
+                        "Mean ± σ/√n",
+                        "Median",
+                        "σ",
+                        "Min",
+                        "Max",
+                        "Weighted mean",
+                    ] {
+                        ui.label(header);
+                    }
+                    ui.end_row();
+
+                    let separator = self.sheet.decimal_separator;
+
+                    for &x in &self.column_order {
+                        let column = &self.sheet.columns[x];
+                        let statistics = self.sheet.column_statistics(x);
+                        let measure = |value: f64, uncertainty: f64| {
+                            if value.is_nan() {
+                                String::new()
+                            } else {
+                                let (value, uncertainty) =
+                                    column.format_measure(value, uncertainty, separator);
+                                format!("{value} ± {uncertainty}")
+                            }
+                        };
+                        let value = |value: f64| {
+                            if value.is_nan() {
+                                String::new()
+                            } else {
+                                column.format_measure(value, 0., separator).0
+                            }
+                        };
+
+                        ui.label(self.sheet.column_title(x));
+                        ui.label(statistics.count.to_string());
+                        ui.label(measure(statistics.mean, statistics.standard_error));
+                        ui.label(value(statistics.median));
+                        ui.label(value(statistics.standard_deviation));
+                        ui.label(value(statistics.min));
+                        ui.label(value(statistics.max));
+                        ui.label(measure(
+                            statistics.weighted_mean,
+                            statistics.weighted_uncertainty,
+                        ));
+                        ui.end_row();
+                    }
+                });
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    fn show_outliers(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("Outliers")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let source_name = |column: Option<usize>| {
+                    column.map_or_else(
+                        || "fit residuals".to_owned(),
+                        |x| self.sheet.columns[x].name.clone(),
+                    )
+                };
+
+                let mut outlier_column = self.outlier_column;
+                egui::ComboBox::from_label("Values")
+                    .selected_text(source_name(outlier_column))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut outlier_column, None, source_name(None));
+
+                        for &x in &self.column_order {
+                            ui.selectable_value(&mut outlier_column, Some(x), source_name(Some(x)));
+                        }
+                    });
+                self.outlier_column = outlier_column;
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.outlier_test, OutlierTest::Chauvenet, "Chauvenet");
+
+                    if ui
+                        .radio(
+                            matches!(self.outlier_test, OutlierTest::Grubbs(_)),
+                            "Grubbs",
+                        )
+                        .clicked()
+                    {
+                        self.outlier_test = OutlierTest::Grubbs(0.05);
+                    }
+
+                    if ui
+                        .radio(matches!(self.outlier_test, OutlierTest::ZScore(_)), "|z|")
+                        .clicked()
+                    {
+                        self.outlier_test = OutlierTest::ZScore(3.);
+                    }
+
+                    match &mut self.outlier_test {
+                        OutlierTest::Chauvenet => {}
+                        OutlierTest::Grubbs(significance) => {
+                            ui.label("α");
+                            ui.add(
+                                DragValue::new(significance)
+                                    .speed(0.001)
+                                    .clamp_range(0.001..=0.5),
+                            );
+                        }
+                        OutlierTest::ZScore(threshold) => {
+                            ui.label("threshold");
+                            ui.add(
+                                DragValue::new(threshold)
+                                    .speed(0.1)
+                                    .clamp_range(0.0..=f64::INFINITY),
+                            );
+                        }
+                    }
+                });
+
+                // Excluded lines are already out of the sample, tests can be repeated
+                let values: Vec<f64> = match self.outlier_column {
+                    Some(x) => self.sheet.grid.iter().map(|line| line[x].value).collect(),
+                    None => self.sheet.fit_residuals(),
+                }
+                .into_iter()
+                .enumerate()
+                .map(|(line_n, value)| {
+                    if self.sheet.line_excluded(line_n) {
+                        f64::NAN
+                    } else {
+                        value
+                    }
+                })
+                .collect();
+
+                let report = self.outlier_test.apply(&values);
+                let separator = self.sheet.decimal_separator;
+                let number = |number: f64| {
+                    separator.localize(format_measure(number, 0., NumberFormat::Significant, 4).0)
+                };
+
+                ui.label(format!(
+                    "n : {}, mean : {}, s : {}",
+                    values.iter().filter(|value| !value.is_nan()).count(),
+                    number(report.mean),
+                    number(report.deviation)
+                ));
+
+                if report.threshold.is_nan() {
+                    ui.label("Not enough values for this test");
+                } else if let OutlierTest::Grubbs(_) = self.outlier_test {
+                    let statistic = report
+                        .scores
+                        .iter()
+                        .copied()
+                        .filter(|score| !score.is_nan())
+                        .fold(0., f64::max);
+
+                    ui.label(format!(
+                        "G = max |x - mean| / s : {}, critical value : {}",
+                        number(statistic),
+                        number(report.threshold)
+                    ));
+                } else {
+                    ui.label(format!(
+                        "Outliers have |x - mean| / s above {}",
+                        number(report.threshold)
+                    ));
+                }
+
+                egui::Grid::new("outliers").striped(true).show(ui, |ui| {
+                    ui.label("Line");
+                    ui.label("Value");
+                    ui.label("|x - mean| / s");
+                    ui.end_row();
+
+                    for &line_n in &report.outliers {
+                        ui.label(RichText::new((line_n + 1).to_string()).color(OUTLIER_COLOR));
+                        ui.label(number(values[line_n]));
+                        ui.label(number(report.scores[line_n]));
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!report.outliers.is_empty(), Button::new("Exclude from fit"))
+                        .clicked()
+                    {
+                        for &line_n in &report.outliers {
+                            self.sheet.set_line_excluded(line_n, true);
+                        }
+                        self.sheet.compute_all();
+                    }
+
+                    if ui.button("Include every line").clicked() {
+                        for line_n in 0..self.sheet.grid.len() {
+                            self.sheet.set_line_excluded(line_n, false);
+                        }
+                        self.sheet.compute_all();
+                    }
+                });
+
+                self.outlier_lines = report.outliers;
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+            self.outlier_lines.clear();
+        }
+    }
+
+    fn show_compatibility(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("Compatibility")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let separator = self.sheet.decimal_separator;
+                let measure = |value: f64, uncertainty: f64| {
+                    let (value, uncertainty) =
+                        format_measure(value, uncertainty, NumberFormat::Significant, 3);
+                    format!(
+                        "{} ± {}",
+                        separator.localize(value),
+                        separator.localize(uncertainty)
+                    )
+                };
+
+                let mut values = Vec::new();
+
+                for (index, quantity) in self.compared.iter_mut().enumerate() {
+                    show_quantity_selector(ui, index, quantity, &self.sheet, &self.column_order);
+
+                    match self.sheet.quantity_value(*quantity) {
+                        Some(value) => {
+                            let unit = value
+                                .dimension
+                                .map_or_else(String::new, |dimension| dimension.to_string());
+
+                            ui.label(format!(
+                                "{} = {} {unit}",
+                                self.sheet.quantity_name(*quantity).unwrap_or_default(),
+                                measure(value.value, value.uncertainty)
+                            ));
+
+                            values.push(value);
+                        }
+                        None => {
+                            ui.label(RichText::new("This value doesn't exist").color(Color32::RED));
+                        }
+                    }
+
+                    ui.separator();
+                }
+
+                ui.horizontal(|ui| {
+                    let [compatible, incompatible] = &mut self.compatibility_thresholds;
+
+                    ui.label("Compatible below");
+                    ui.add(
+                        DragValue::new(compatible)
+                            .speed(0.1)
+                            .clamp_range(0.0..=f64::INFINITY),
+                    );
+                    ui.label("incompatible above");
+                    ui.add(
+                        DragValue::new(incompatible)
+                            .speed(0.1)
+                            .clamp_range(*compatible..=f64::INFINITY),
+                    );
+                });
+
+                let [a, b] = values.as_slice() else {
+                    return;
+                };
+
+                if let (Some(a), Some(b)) = (a.dimension, b.dimension) {
+                    if a != b {
+                        ui.label(
+                            RichText::new(format!("The values have different units, {a} and {b}"))
+                                .color(Color32::RED),
+                        );
+                    }
+                }
+
+                let compatibility =
+                    Compatibility::new((a.value, a.uncertainty), (b.value, b.uncertainty));
+
+                ui.label(format!(
+                    "|a - b| = {}",
+                    measure(compatibility.difference, compatibility.combined_uncertainty)
+                ));
+                ui.label(format!(
+                    "|a - b| / √(Δa² + Δb²) = {}",
+                    separator.localize(format!("{:.2}", compatibility.normalized_difference))
+                ));
+                ui.label(if compatibility.overlap {
+                    "The uncertainty intervals overlap"
+                } else {
+                    "The uncertainty intervals don't overlap"
+                });
+
+                let [compatible, incompatible] = self.compatibility_thresholds;
+                let (verdict, color) = match compatibility.verdict(compatible, incompatible) {
+                    Verdict::Compatible => ("Compatible", Color32::from_rgb(63, 191, 63)),
+                    Verdict::Tension => ("In tension", OUTLIER_COLOR),
+                    Verdict::Incompatible => ("Incompatible", Color32::RED),
+                };
+
+                ui.label(RichText::new(verdict).strong().color(color));
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    fn show_functions(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("Functions")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("One function per line, a function can call the ones above it");
+
+                let library_input = ui.add(
+                    TextEdit::multiline(&mut self.sheet.function_library)
+                        .code_editor()
+                        .desired_rows(8)
+                        .hint_text("ohm(u, i) = u / i\ndeg(x) = x * 180 / pi"),
+                );
+
+                if library_input.changed() {
+                    self.sheet.compute_all();
+                }
+
+                for error in &self.sheet.function_errors {
+                    ui.label(RichText::new(error).color(Color32::RED));
+                }
+
+                if ui.button("copy library").clicked() {
+                    ui.output_mut(|output| {
+                        output.copied_text.clone_from(&self.sheet.function_library);
+                    });
+                }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.library_path).desired_width(150.));
+
+                    if ui.button("load").clicked() {
+                        match std::fs::read_to_string(&self.library_path) {
+                            Ok(library) => {
+                                self.sheet.function_library = library;
+                                self.library_message.clear();
+                                self.sheet.compute_all();
+                            }
+                            Err(error) => self.library_message = error.to_string(),
+                        }
+                    }
+
+                    if ui.button("save").clicked() {
+                        self.library_message = match std::fs::write(
+                            &self.library_path,
+                            &self.sheet.function_library,
+                        ) {
+                            Ok(()) => format!("saved to {}", self.library_path),
+                            Err(error) => error.to_string(),
+                        };
+                    }
+                });
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if !self.library_message.is_empty() {
+                    ui.label(&self.library_message);
+                }
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    fn show_global_settings(&mut self, ctx: &Context) {
+        let mut open = true;
+        Window::new("⚙ settings")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.vertical(|ui| {
+                    ui.label("Box plot orientation");
+                    ui.radio_value(&mut self.vertical_box_plot, true, "Vertical");
+                    ui.radio_value(&mut self.vertical_box_plot, false, "Horizontal");
+                    ui.label("Box plot style");
+                    ui.radio_value(&mut self.full_box_plot, true, "Full box plot");
+                    ui.radio_value(&mut self.full_box_plot, false, "Whisker box plot");
+                    ui.label("Regression type");
+
+                    let mut fit_changed = false;
+                    fit_changed |= ui
+                        .radio_value(&mut self.sheet.linear_regression, true, "Linear regression")
+                        .changed();
+                    fit_changed |= ui
+                        .radio_value(
+                            &mut self.sheet.linear_regression,
+                            false,
+                            "Affine regression",
+                        )
+                        .changed();
+
+                    ui.label("Fit name in expressions");
+                    fit_changed |= ui
+                        .add(TextEdit::singleline(&mut self.sheet.fit_name).hint_text("fit"))
+                        .on_hover_text(format!(
+                            "Parameters are read as {0}.slope and {0}.height",
+                            self.sheet.fit_name.trim()
+                        ))
+                        .changed();
+
+                    if fit_changed {
+                        self.sheet.compute_all();
+                    }
+
+                    ui.label("Decimal separator");
+
+                    let mut changed = false;
+                    for (separator, text) in [
+                        (DecimalSeparator::Auto, "Auto (point or comma)"),
+                        (DecimalSeparator::Point, "Point"),
+                        (DecimalSeparator::Comma, "Comma"),
+                    ] {
+                        changed |= ui
+                            .radio_value(&mut self.sheet.decimal_separator, separator, text)
+                            .changed();
+                    }
+
+                    if changed {
+                        self.sheet.compute_and_parse_all();
+                    }
+                })
+            });
+
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    fn show_column_settings(&mut self, ctx: &Context, column_index: usize) {
+        let mut open = true;
+        Window::new("⚙ column settings")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.vertical(|ui| {
+                    ui.label("Name");
+
+                    if column_index > 1 {
+                        let text_edit =
+                            TextEdit::singleline(&mut self.sheet.columns[column_index].name);
+
+                        let name_input = ui.add(text_edit);
+
+                        if name_input.lost_focus() {
+                            trim_in_place(&mut self.sheet.columns[column_index].name);
+                        }
+                    } else {
+                        ui.label(self.sheet.columns[column_index].name.clone());
+                    }
+
+                    if let Some(error) = self.sheet.column_name_error(column_index) {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+
+                    self.show_calibration_settings(ui, column_index);
+
+                    ui.label("Expression");
+
+                    let error = self.sheet.expression_error(column_index);
+                    let calibrated = self.sheet.columns[column_index].calibration.is_some();
+                    let expression_id = Id::new(("expression", column_index));
+
+                    // Tab completes the names instead of moving the focus
+                    let mut text_edit =
+                        TextEdit::singleline(&mut self.sheet.columns[column_index].expression)
+                            .id(expression_id)
+                            .lock_focus(true);
+
+                    if error.is_some() {
+                        text_edit = text_edit.text_color(Color32::RED);
+                    }
+
+                    // The expression of a calibration is generated from it
+                    let output = ui
+                        .add_enabled_ui(!calibrated, |ui| text_edit.show(ui))
+                        .inner;
+                    let expression_input = output.response.on_hover_text(EXPRESSION_HELP);
+
+                    if expression_input.lost_focus() {
+                        trim_in_place(&mut self.sheet.columns[column_index].expression);
+                    }
+
+                    let completed = self.complete_expression(
+                        ui,
+                        column_index,
+                        &expression_input,
+                        output.state.ccursor_range(),
+                    );
+
+                    if expression_input.changed() || completed {
+                        self.sheet.compute_all();
+                    }
+
+                    if let Some(error) = error {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+
+                    ui.label("Unit");
+
+                    let unit_input = ui.add(
+                        TextEdit::singleline(&mut self.sheet.columns[column_index].unit)
+                            .hint_text("kg·m/s²"),
+                    );
+
+                    if unit_input.changed() {
+                        self.sheet.columns[column_index].update_unit_factor();
+                        self.sheet.compute_and_parse_all();
+                    }
+
+                    match self.sheet.column_dimension(column_index) {
+                        Err(error) => {
+                            ui.label(RichText::new(error).color(Color32::RED));
+                        }
+                        Ok(dimension)
+                            if !self.sheet.columns[column_index].expression.is_empty()
+                                && self.sheet.columns[column_index].unit.trim().is_empty() =>
+                        {
+                            ui.label(format!("Result in {dimension}"));
+                        }
+                        Ok(_) => {}
+                    }
+
+                    ui.label("Format");
+
+                    let format = &mut self.sheet.columns[column_index].format;
+                    ui.radio_value(format, NumberFormat::Fixed, "Fixed decimals");
+                    ui.radio_value(format, NumberFormat::Significant, "Significant figures");
+                    ui.radio_value(format, NumberFormat::Scientific, "Scientific");
+                    ui.radio_value(format, NumberFormat::Engineering, "Engineering");
+
+                    ui.label(if *format == NumberFormat::Significant {
+                        "Significant figures without uncertainty"
+                    } else {
+                        "Precision"
+                    });
+
+                    let precision_edit =
+                        DragValue::new(&mut self.sheet.columns[column_index].precision)
+                            .clamp_range(0..=10);
+
+                    ui.add(precision_edit);
+
+                    if self.sheet.columns[column_index].expression.is_empty() {
+                        self.show_uncertainty_model_settings(ui, column_index);
+                        self.show_fill_settings(ui, column_index);
+                    }
+
+                    let column = &mut self.sheet.columns[column_index];
+                    ui.checkbox(&mut column.hidden, "Hide column");
+                    ui.checkbox(&mut column.uncertainty_hidden, "Hide Δ column");
+
+                    let duplication_error = self.sheet.duplication_error(column_index);
+
+                    if ui
+                        .add_enabled(duplication_error.is_none(), Button::new("duplicate column"))
+                        .on_disabled_hover_text(duplication_error.unwrap_or_default())
+                        .clicked()
+                    {
+                        self.duplicate_column(column_index);
+                    }
+
+                    if column_index > 1 && ui.button("remove column").clicked() {
+                        self.popup_status = PopupStatus::None;
+                        self.remove_column(column_index);
+                    }
+                });
+            });
+        if !open {
+            self.popup_status = PopupStatus::None;
+        }
+    }
+
+    /// Proposes the names starting like the identifier before the cursor of the expression
+    /// field, clicking a name or pressing Tab completes the identifier, returns whether the
+    /// expression was completed
+    fn complete_expression(
+        &mut self,
+        ui: &mut egui::Ui,
+        column_index: usize,
+        input: &egui::Response,
+        cursor: Option<CCursorRange>,
+    ) -> bool {
+        // The field has already lost the focus when a name is clicked
+        if !input.has_focus() && !input.lost_focus() {
+            return false;
+        }
+
+        let Some(cursor) = cursor else {
+            return false;
+        };
+
+        let expression = &self.sheet.columns[column_index].expression;
+        let end = cursor.primary.index.min(expression.chars().count());
+        let start = identifier_start(expression, end);
+        let prefix: String = expression.chars().skip(start).take(end - start).collect();
+
+        let names = self.sheet.completion_names(column_index);
+        let completions = completions(&prefix, &names);
+
+        if completions.is_empty() {
+            return false;
+        }
+
+        let mut chosen = (input.has_focus()
+            && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab)))
+        .then(|| completions[0]);
+
+        ui.horizontal_wrapped(|ui| {
+            for completion in completions.iter().take(COMPLETION_COUNT) {
+                if ui.small_button(*completion).clicked() {
+                    chosen = Some(completion);
+                }
+            }
+        });
+
+        let Some(chosen) = chosen else {
+            return false;
+        };
+
+        let expression = &mut self.sheet.columns[column_index].expression;
+        let byte_index = |n: usize| {
+            expression
+                .char_indices()
+                .nth(n)
+                .map_or(expression.len(), |(index, _)| index)
+        };
+        let range = byte_index(start)..byte_index(end);
+        expression.replace_range(range, chosen);
+
+        let mut state = TextEditState::load(ui.ctx(), input.id).unwrap_or_default();
+        state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(
+            start + chosen.chars().count(),
+        ))));
+        state.store(ui.ctx(), input.id);
+        input.request_focus();
+
+        true
+    }
+
+    /// Turns a column into the inverse of a fit applied to a column of readings
+    fn show_calibration_settings(&mut self, ui: &mut egui::Ui, column_index: usize) {
+        ui.label("Calibration");
+
+        let sources = self.sheet.calibration_sources();
+        let mut calibration = self.sheet.columns[column_index].calibration.clone();
+
+        // Like any expression, the calibration can only read the columns computed before it
+        let readable: Vec<usize> = self
+            .column_order
+            .iter()
+            .copied()
+            .filter(|x| *x > column_index)
+            .collect();
+
+        ComboBox::from_id_source(("calibration_fit", column_index))
+            .selected_text(
+                calibration
+                    .as_ref()
+                    .map_or("None", |calibration| calibration.fit.as_str()),
+            )
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(calibration.is_none(), "None").clicked() {
+                    calibration = None;
+                }
+
+                for fit in sources {
+                    let selected = calibration
+                        .as_ref()
+                        .is_some_and(|calibration| calibration.fit == fit);
+
+                    if ui.selectable_label(selected, &fit).clicked() {
+                        // The readings default to the first readable column
+                        let reading = calibration.take().map_or_else(
+                            || {
+                                readable.first().map_or_else(String::new, |x| {
+                                    self.sheet.columns[*x].name.clone()
+                                })
+                            },
+                            |calibration| calibration.reading,
+                        );
+
+                        calibration = Some(Calibration { fit, reading });
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Gives (reading - height) / slope for a fit slope * x + height");
+
+        if let Some(calibration) = &mut calibration {
+            if readable.is_empty() {
+                ui.label(
+                    RichText::new(
+                        "The other columns are computed after this one, none can be the readings",
+                    )
+                    .color(Color32::RED),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Readings");
 
-        if app.columns.is_empty() {
-            app.add_column("y".to_owned());
-            app.add_column("x".to_owned());
+                ComboBox::from_id_source(("calibration_reading", column_index))
+                    .selected_text(calibration.reading.as_str())
+                    .show_ui(ui, |ui| {
+                        for &x in &readable {
+                            let name = &self.sheet.columns[x].name;
+
+                            if ui
+                                .selectable_label(calibration.reading == *name, name)
+                                .clicked()
+                            {
+                                calibration.reading.clone_from(name);
+                            }
+                        }
+                    });
+            });
         }
 
-        app
-    }
+        let column = &mut self.sheet.columns[column_index];
 
-    fn add_column(&mut self, name: String) {
-        self.columns.push(ColumnSettings::new(name));
+        if column.calibration != calibration {
+            if let Some(calibration) = &calibration {
+                column.expression = calibration.expression();
+            }
 
-        for line in &mut self.grid {
-            line.push(Value::new());
+            column.calibration = calibration;
+            self.sheet.compute_all();
         }
     }
 
-    fn remove_column(&mut self, index: usize) {
-        self.columns.remove(index);
-
-        for line in &mut self.grid {
-            line.remove(index);
+    fn show_uncertainty_model_settings(&mut self, ui: &mut egui::Ui, column_index: usize) {
+        let separator = self.sheet.decimal_separator;
+        let column = &mut self.sheet.columns[column_index];
+        let mut changed = false;
+
+        ui.label("Default uncertainty");
+
+        for (model, text) in [
+            (UncertaintyModel::PerCell, "Per cell"),
+            (UncertaintyModel::Constant, "Constant"),
+            (UncertaintyModel::Formula, "Formula of value"),
+            (UncertaintyModel::TypeAB, "Type A + type B"),
+        ] {
+            changed |= ui
+                .radio_value(&mut column.uncertainty_model, model, text)
+                .changed();
         }
-    }
 
-    fn add_line(&mut self) {
-        self.grid.push(vec![Value::new(); self.columns.len()]);
-    }
+        match column.uncertainty_model {
+            UncertaintyModel::PerCell => {}
+            UncertaintyModel::Constant => {
+                let invalid = separator.parse(&column.uncertainty_constant).is_none();
 
-    fn ensure_empty_line(&mut self) {
-        let mut last_empty_line = 0;
+                let mut text_edit = TextEdit::singleline(&mut column.uncertainty_constant);
 
-        'outer: for i in (0..self.grid.len()).rev() {
-            for x in 0..self.columns.len() {
-                if self.columns[x].expression.is_empty()
-                    && (!self.grid[i][x].raw_value.is_empty()
-                        || !self.grid[i][x].raw_uncertainty.is_empty())
-                {
-                    last_empty_line = i + 1;
-                    break 'outer;
+                if invalid {
+                    text_edit = text_edit.text_color(Color32::RED);
                 }
-            }
-        }
 
-        if last_empty_line >= self.grid.len() {
-            self.add_line();
-        } else {
-            self.grid.drain((last_empty_line + 1)..self.grid.len());
-        }
-    }
-
-    fn compute_line_value(&mut self, line_n: usize) {
-        for column_n in (0..self.columns.len()).rev() {
-            if !self.columns[column_n].expression.is_empty() {
-                let mut context = HashMapContext::new();
-
-                for i in (column_n + 1)..self.columns.len() {
-                    context
-                        .set_value(
-                            self.columns[i].name.clone(),
-                            self.grid[line_n][i].value.into(),
-                        )
-                        .unwrap();
+                changed |= ui.add(text_edit).changed();
+            }
+            UncertaintyModel::Formula | UncertaintyModel::TypeAB => {
+                if column.uncertainty_model == UncertaintyModel::TypeAB {
+                    ui.label("Type B formula (optional)");
                 }
 
-                let result = eval_number_with_context(&self.columns[column_n].expression, &context);
-
-                self.grid[line_n][column_n].value = result.unwrap_or(f64::NAN);
-            }
-        }
-    }
+                let invalid = (column.uncertainty_model == UncertaintyModel::Formula
+                    || !column.uncertainty_formula.trim().is_empty())
+                    && column.formula_uncertainty(1.).is_nan();
 
-    fn compute_line_with_uncertainty(&mut self, line_n: usize) {
-        self.compute_line_value(line_n);
+                let mut text_edit = TextEdit::singleline(&mut column.uncertainty_formula)
+                    .hint_text("0.005*value + 0.01");
 
-        let mut reference_values: Vec<f64> = Vec::new();
+                if invalid {
+                    text_edit = text_edit.text_color(Color32::RED);
+                }
 
-        for column_n in 0..self.columns.len() {
-            if self.columns[column_n].expression.is_empty() {
-                reference_values.push(self.grid[line_n][column_n].value);
+                changed |= ui.add(text_edit).changed();
             }
         }
 
-        if reference_values.is_empty() {
-            return;
+        if changed {
+            self.sheet.compute_and_parse_all();
         }
+    }
 
-        let mut samplers = vec![-SAMPLE_COUNT; reference_values.len()];
-
-        let mut maxs = vec![f64::NAN; self.columns.len() - reference_values.len()];
-        let mut mins = vec![f64::NAN; self.columns.len() - reference_values.len()];
+    fn show_fill_settings(&mut self, ui: &mut egui::Ui, column_index: usize) {
+        ui.collapsing("Fill", |ui| {
+            let fill = &mut self.fill;
 
-        'outer: loop {
-            let mut index = 0;
+            ui.radio_value(&mut fill.kind, FillKind::Linear, "Linear series");
+            ui.radio_value(&mut fill.kind, FillKind::Geometric, "Geometric series");
+            ui.radio_value(&mut fill.kind, FillKind::Constant, "Constant");
+            ui.radio_value(&mut fill.kind, FillKind::Expression, "Expression of line i");
 
-            for column_n in 0..self.columns.len() {
-                if self.columns[column_n].expression.is_empty() {
-                    self.grid[line_n][column_n].value = reference_values[index]
-                        + samplers[index] as f64 * self.grid[line_n][column_n].uncertainty
-                            / SAMPLE_COUNT as f64;
+            let values = fill.values(self.sheet.decimal_separator);
+            let invalid = |field| values.as_ref().err() == Some(&field);
 
-                    index += 1;
+            if fill.kind == FillKind::Expression {
+                let mut text_edit = TextEdit::singleline(&mut fill.expression).hint_text("2^i");
+                if invalid("expression") {
+                    text_edit = text_edit.text_color(Color32::RED);
                 }
-            }
-
-            self.compute_line_value(line_n);
-
-            index = 0;
-
-            for column_n in 0..self.columns.len() {
-                if !self.columns[column_n].expression.is_empty() {
-                    maxs[index] = maxs[index].max(self.grid[line_n][column_n].value);
-                    mins[index] = mins[index].min(self.grid[line_n][column_n].value);
-                    index += 1;
+                ui.add(text_edit);
+            } else {
+                ui.label(if fill.kind == FillKind::Constant {
+                    "Value"
+                } else {
+                    "Start"
+                });
+                let mut text_edit = TextEdit::singleline(&mut fill.start);
+                if invalid("start") {
+                    text_edit = text_edit.text_color(Color32::RED);
                 }
+                ui.add(text_edit);
             }
 
-            for i in (0..samplers.len()).rev() {
-                if samplers[i] < SAMPLE_COUNT {
-                    samplers[i] += 1;
-                    break;
-                } else if i == 0 {
-                    break 'outer;
+            if matches!(fill.kind, FillKind::Linear | FillKind::Geometric) {
+                ui.label(if fill.kind == FillKind::Linear {
+                    "Step"
+                } else {
+                    "Ratio"
+                });
+                let mut text_edit = TextEdit::singleline(&mut fill.step);
+                if invalid("step") {
+                    text_edit = text_edit.text_color(Color32::RED);
                 }
-                samplers[i] = -SAMPLE_COUNT;
+                ui.add(text_edit);
             }
-        }
 
-        let mut index = self.columns.len() - reference_values.len();
-        for column_n in (0..self.columns.len()).rev() {
-            if self.columns[column_n].expression.is_empty() {
-                self.grid[line_n][column_n].value = reference_values.pop().unwrap();
-            } else {
-                index -= 1;
-                self.grid[line_n][column_n].uncertainty = (maxs[index] - mins[index]) / 2.;
-            }
-        }
+            ui.label("Lines");
+            ui.add(DragValue::new(&mut fill.count).clamp_range(1..=10000));
 
-        self.compute_line_value(line_n);
+            if ui
+                .add_enabled(values.is_ok(), Button::new("fill"))
+                .clicked()
+            {
+                if let Ok(values) = values {
+                    self.sheet.fill_column(column_index, &values);
+                }
+            }
+        });
     }
+}
 
-    fn compute_all(&mut self) {
-        for line_n in 0..self.grid.len() {
-            self.compute_line_with_uncertainty(line_n);
+/// Chooses a cell, a fit parameter or a constant of the sheet
+fn show_quantity_selector(
+    ui: &mut egui::Ui,
+    index: usize,
+    quantity: &mut Quantity,
+    sheet: &Sheet,
+    column_order: &[usize],
+) {
+    ui.horizontal(|ui| {
+        if ui
+            .radio(matches!(quantity, Quantity::Cell { .. }), "Cell")
+            .clicked()
+        {
+            *quantity = Quantity::Cell { line: 0, column: 0 };
         }
-    }
 
-    fn compute_and_parse_all(&mut self) {
-        for line_n in 0..self.grid.len() {
-            for column_n in 0..self.columns.len() {
-                self.grid[line_n][column_n].value = self.grid[line_n][column_n]
-                    .raw_value
-                    .trim()
-                    .parse::<f64>()
-                    .unwrap_or(f64::NAN);
-                self.grid[line_n][column_n].uncertainty = self.grid[line_n][column_n]
-                    .raw_uncertainty
-                    .trim()
-                    .parse::<f64>()
-                    .unwrap_or(f64::NAN);
-            }
+        if ui
+            .radio(matches!(quantity, Quantity::FitParameter(_)), "Fit")
+            .clicked()
+        {
+            *quantity = Quantity::FitParameter(0);
         }
 
-        self.compute_all();
-    }
-
-    fn show_global_settings(&mut self, ctx: &Context) {
-        let mut open = true;
-        Window::new("⚙ settings")
-            .collapsible(false)
-            .open(&mut open)
-            .show(ctx, |ui| {
-                ui.vertical(|ui| {
-                    ui.label("Box plot orientation");
-                    ui.radio_value(&mut self.vertical_box_plot, true, "Vertical");
-                    ui.radio_value(&mut self.vertical_box_plot, false, "Horizontal");
-                    ui.label("Box plot style");
-                    ui.radio_value(&mut self.full_box_plot, true, "Full box plot");
-                    ui.radio_value(&mut self.full_box_plot, false, "Whisker box plot");
-                    ui.label("Regression type");
-                    ui.radio_value(&mut self.linear_regression, true, "Linear regression");
-                    ui.radio_value(&mut self.linear_regression, false, "Affine regression");
-                })
-            });
-
-        if !open {
-            self.popup_status = PopupStatus::None;
+        if ui
+            .radio(matches!(quantity, Quantity::Constant(_)), "Constant")
+            .clicked()
+        {
+            *quantity = Quantity::Constant(0);
         }
-    }
-
-    fn show_column_settings(&mut self, ctx: &Context, column_index: usize) {
-        let mut open = true;
-        Window::new("⚙ column settings")
-            .collapsible(false)
-            .open(&mut open)
-            .show(ctx, |ui| {
-                ui.vertical(|ui| {
-                    let mut context = HashMapContext::new();
-
-                    for i in (column_index + 1)..self.columns.len() {
-                        context
-                            .set_value(self.columns[i].name.clone(), f64::NAN.into())
-                            .unwrap();
-                    }
-
-                    ui.label("Name");
-
-                    if column_index > 1 {
-                        let text_edit = TextEdit::singleline(&mut self.columns[column_index].name);
-
-                        let name_input = ui.add(text_edit);
-
-                        if name_input.lost_focus() {
-                            self.columns[column_index].name =
-                                self.columns[column_index].name.trim().to_owned();
-                        }
-                    } else {
-                        ui.label(self.columns[column_index].name.clone());
-                    }
-
-                    ui.label("Expression");
+    });
 
-                    let result: Result<f64, EvalexprError> =
-                        eval_number_with_context(&self.columns[column_index].expression, &context);
+    ui.horizontal(|ui| match quantity {
+        Quantity::Cell { line, column } => {
+            let name = |x: usize| sheet.columns.get(x).map_or("", |column| &column.name);
 
-                    let mut text_edit =
-                        TextEdit::singleline(&mut self.columns[column_index].expression);
-
-                    if result.is_err() {
-                        text_edit = text_edit.text_color(Color32::RED);
+            egui::ComboBox::from_id_source(("compared_column", index))
+                .selected_text(name(*column))
+                .show_ui(ui, |ui| {
+                    for &x in column_order {
+                        ui.selectable_value(column, x, name(x));
                     }
+                });
 
-                    let expression_input = ui.add(text_edit);
-
-                    if expression_input.lost_focus() {
-                        self.columns[column_index].expression =
-                            self.columns[column_index].expression.trim().to_owned();
+            let mut number = *line + 1;
+            ui.label("line");
+            ui.add(DragValue::new(&mut number).clamp_range(1..=sheet.filled_line_count().max(1)));
+            *line = number - 1;
+        }
+        Quantity::FitParameter(parameter_n) => {
+            egui::ComboBox::from_id_source(("compared_parameter", index))
+                .selected_text(
+                    FIT_PARAMETERS
+                        .get(*parameter_n)
+                        .copied()
+                        .unwrap_or_default(),
+                )
+                .show_ui(ui, |ui| {
+                    for (n, parameter) in FIT_PARAMETERS.iter().enumerate() {
+                        ui.selectable_value(parameter_n, n, *parameter);
                     }
-
-                    if expression_input.changed() {
-                        self.compute_all();
+                });
+        }
+        Quantity::Constant(constant_n) => {
+            let name = |n: usize| sheet.constants.get(n).map_or("", |constant| &constant.name);
+
+            egui::ComboBox::from_id_source(("compared_constant", index))
+                .selected_text(name(*constant_n))
+                .show_ui(ui, |ui| {
+                    for n in 0..sheet.constants.len() {
+                        ui.selectable_value(constant_n, n, name(n));
                     }
+                });
+        }
+    });
+}
 
-                    ui.label("Precision");
-
-                    let precision_edit = DragValue::new(&mut self.columns[column_index].precision)
-                        .clamp_range(0..=10);
+/// Label of a tick of a plot axis showing `range`, ticks are never closer than a hundredth of
+/// the range so the value is rounded to that precision
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn axis_label(
+    value: f64,
+    range: &RangeInclusive<f64>,
+    separator: DecimalSeparator,
+    unit: &str,
+) -> String {
+    let step = (range.end() - range.start()).abs() / 100.;
+    let decimals = if step.is_finite() && step > 0. {
+        (-step.log10().floor()).max(0.) as usize
+    } else {
+        0
+    };
+
+    // Adding zero turns a rounded -0 into 0
+    let rounded = egui::emath::round_to_decimals(value, decimals) + 0.;
+
+    format!("{} {unit}", separator.localize(rounded.to_string()))
+}
 
-                    ui.add(precision_edit);
+/// Removes the whitespace around a text typed by the user
+fn trim_in_place(text: &mut String) {
+    text.truncate(text.trim_end().len());
+    text.drain(..text.len() - text.trim_start().len());
+}
 
-                    if column_index > 1 && ui.button("remove column").clicked() {
-                        self.popup_status = PopupStatus::None;
-                        self.remove_column(column_index);
-                    }
-                });
-            });
-        if !open {
-            self.popup_status = PopupStatus::None;
+fn surrender_focus(ctx: &Context) {
+    ctx.memory_mut(|memory| {
+        if let Some(id) = memory.focus() {
+            memory.surrender_focus(id);
         }
-    }
+    });
 }
 
 impl eframe::App for App {
     fn save(&mut self, storage: &mut dyn Storage) {
         storage.set_string(DARK_THEME_KEY, self.dark_theme.to_string());
-        storage.set_string(VERTICAL_BOX_PLOT_KEY, self.vertical_box_plot.to_string());
-        storage.set_string(FULL_BOX_PLOT_KEY, self.full_box_plot.to_string());
-        storage.set_string(LINEAR_REGRESSION_KEY, self.linear_regression.to_string());
-
-        storage.set_string(COLUMN_COUNT_KEY, self.columns.len().to_string());
-        storage.set_string(LINE_COUNT_KEY, self.grid.len().to_string());
 
-        for column_n in 0..self.columns.len() {
-            storage.set_string(
-                &format!("{COLUMN_NAME_KEY}_{column_n}"),
-                self.columns[column_n].name.clone(),
-            );
-
-            storage.set_string(
-                &format!("{COLUMN_EXPRESSION_KEY}_{column_n}"),
-                self.columns[column_n].expression.clone(),
-            );
+        let names: Vec<&str> = self.tabs.iter().map(|tab| tab.name.as_str()).collect();
+        save_sheet_names(&names, &mut |key, value| storage.set_string(key, value));
 
-            storage.set_string(
-                &format!("{COLUMN_PRECISION_KEY}_{column_n}"),
-                self.columns[column_n].precision.to_string(),
-            );
+        self.swap_tab(self.current_tab);
 
-            for line_n in 0..self.grid.len() {
-                storage.set_string(
-                    &format!("{GRID_VALUE_KEY}_{line_n}_{column_n}"),
-                    self.grid[line_n][column_n].raw_value.clone(),
-                );
-                storage.set_string(
-                    &format!("{GRID_UNCERTAINTY_KEY}_{line_n}_{column_n}"),
-                    self.grid[line_n][column_n].raw_uncertainty.clone(),
-                );
-            }
+        for (tab_n, tab) in self.tabs.iter().enumerate() {
+            let prefix = sheet_prefix(tab_n);
+            tab.save(&mut |key, value| storage.set_string(&format!("{prefix}{key}"), value));
         }
 
+        self.swap_tab(self.current_tab);
+
         // Prevent eframe from saving unneeded data
         storage.set_string("egui", String::new());
         storage.set_string("window", String::new());
@@ -479,8 +2481,41 @@ impl eframe::App for App {
             Visuals::light()
         });
 
+        // Consumed before drawing so text fields don't apply their own undo
+        if ctx.input_mut(|i| {
+            i.consume_shortcut(&REDO_SHORTCUT) || i.consume_shortcut(&REDO_ALTERNATIVE_SHORTCUT)
+        }) {
+            self.redo(ctx);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.undo(ctx);
+        }
+
         TopBottomPanel::top("settings_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |bar_ui| {
+                bar_ui.menu_button("Edit", |ui| {
+                    let can_undo =
+                        self.history.can_undo() || !self.matches_snapshot(self.history.current());
+
+                    let undo_button =
+                        Button::new("Undo").shortcut_text(ctx.format_shortcut(&UNDO_SHORTCUT));
+
+                    if ui.add_enabled(can_undo, undo_button).clicked() {
+                        self.undo(ctx);
+                        ui.close_menu();
+                    }
+
+                    let redo_button =
+                        Button::new("Redo").shortcut_text(ctx.format_shortcut(&REDO_SHORTCUT));
+
+                    if ui
+                        .add_enabled(self.history.can_redo(), redo_button)
+                        .clicked()
+                    {
+                        self.redo(ctx);
+                        ui.close_menu();
+                    }
+                });
+
                 if bar_ui
                     .button(if self.dark_theme { "Light" } else { "Dark" })
                     .clicked()
@@ -488,26 +2523,179 @@ impl eframe::App for App {
                     self.dark_theme = !self.dark_theme;
                 }
 
+                bar_ui.menu_button("Columns", |ui| {
+                    for &x in &self.column_order {
+                        let column = &mut self.sheet.columns[x];
+
+                        ui.horizontal(|ui| {
+                            let mut shown = !column.hidden;
+                            if ui.checkbox(&mut shown, column.name.clone()).changed() {
+                                column.hidden = !shown;
+                            }
+
+                            let mut uncertainty_shown = !column.uncertainty_hidden;
+                            if ui.checkbox(&mut uncertainty_shown, "Δ").changed() {
+                                column.uncertainty_hidden = !uncertainty_shown;
+                            }
+
+                            if ui.button("⚙").clicked() {
+                                self.popup_status = PopupStatus::ColumnSettings(x);
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                });
+
+                if bar_ui.button("Constants").clicked() {
+                    self.popup_status = PopupStatus::Constants;
+                }
+
+                if bar_ui.button("Functions").clicked() {
+                    self.popup_status = PopupStatus::Functions;
+                }
+
+                if bar_ui.button("Statistics").clicked() {
+                    self.popup_status = PopupStatus::Statistics;
+                }
+
+                if bar_ui.button("Outliers").clicked() {
+                    self.popup_status = PopupStatus::Outliers;
+                }
+
+                if bar_ui.button("Compatibility").clicked() {
+                    self.popup_status = PopupStatus::Compatibility;
+                }
+
                 if bar_ui.button("Settings").clicked() {
                     self.popup_status = PopupStatus::GlobalSettings;
                 }
             })
         });
 
+        TopBottomPanel::top("tabs_panel").show(ctx, |ui| {
+            let mut tab_action = None;
+
+            ui.horizontal(|ui| {
+                let removable = self.tabs.len() > 1;
+
+                for tab_n in 0..self.tabs.len() {
+                    if let Some((renamed_n, previous_name)) = &self.renamed_tab {
+                        if *renamed_n == tab_n {
+                            let response = ui
+                                .add(
+                                    TextEdit::singleline(&mut self.tabs[tab_n].name)
+                                        .desired_width(80.),
+                                )
+                                .on_hover_text(
+                                    "Letters, digits, spaces and _, not starting with a digit, \
+                                     a name that can't be read by expressions is not kept",
+                                );
+
+                            if response.lost_focus() {
+                                let identifier = sheet_identifier(&self.tabs[tab_n].name);
+
+                                // Expressions read the values of the sheet through its name,
+                                // and couldn't tell apart sheets with the same name
+                                if !is_valid_sheet_name(&self.tabs[tab_n].name)
+                                    || self.tabs.iter().enumerate().any(|(other_n, other)| {
+                                        other_n != tab_n
+                                            && sheet_identifier(&other.name) == identifier
+                                    })
+                                {
+                                    self.tabs[tab_n].name.clone_from(previous_name);
+                                }
+
+                                self.rename_sheet_references(
+                                    &sheet_identifier(previous_name),
+                                    &sheet_identifier(&self.tabs[tab_n].name.clone()),
+                                );
+
+                                self.renamed_tab = None;
+                                self.link_sheets();
+                            } else if !response.has_focus() {
+                                response.request_focus();
+                            }
+
+                            continue;
+                        }
+                    }
+
+                    let response =
+                        ui.selectable_label(tab_n == self.current_tab, &self.tabs[tab_n].name);
+
+                    if response.clicked() {
+                        tab_action = Some(TabAction::Select(tab_n));
+                    }
+
+                    response.context_menu(|ui| {
+                        if ui.button("Rename").clicked() {
+                            self.renamed_tab = Some((tab_n, self.tabs[tab_n].name.clone()));
+                            ui.close_menu();
+                        }
+
+                        if ui.add_enabled(removable, Button::new("Remove")).clicked() {
+                            tab_action = Some(TabAction::Remove(tab_n));
+                            ui.close_menu();
+                        }
+                    });
+                }
+
+                if ui.button("+").on_hover_text("Add a sheet").clicked() {
+                    tab_action = Some(TabAction::Add);
+                }
+            });
+
+            if let Some(action) = tab_action {
+                self.renamed_tab = None;
+                self.apply_tab_action(ctx, action);
+            }
+        });
+
         match self.popup_status {
             PopupStatus::ColumnSettings(column_index) => {
                 self.show_column_settings(ctx, column_index);
             }
             PopupStatus::GlobalSettings => self.show_global_settings(ctx),
+            PopupStatus::Constants => self.show_constants(ctx),
+            PopupStatus::Functions => self.show_functions(ctx),
+            PopupStatus::Statistics => self.show_statistics(ctx),
+            PopupStatus::Outliers => self.show_outliers(ctx),
+            PopupStatus::Compatibility => self.show_compatibility(ctx),
             PopupStatus::None => {}
         }
 
+        // Blocks of cells are pasted by the table, not by the focused text field
+        let pasted_block = ctx.input_mut(|i| {
+            let mut pasted_block = None;
+
+            i.events.retain(|event| match event {
+                Event::Paste(text) if text.trim_end().contains(['\t', '\n']) => {
+                    pasted_block = Some(text.clone());
+                    false
+                }
+                _ => true,
+            });
+
+            pasted_block
+        });
+
+        if let Some(text) = pasted_block {
+            self.paste_block(&text);
+        }
+
         CentralPanel::default().show(ctx, |ui| {
-            let column_count = self.columns.len();
+            let cells = self.visible_cells();
+            let selection = self.selection_bounds(&cells).filter(|(lines, positions)| {
+                lines.start() != lines.end() || positions.start() != positions.end()
+            });
+            let mut line_action = None;
+            let mut column_action = None;
+
+            self.focused_cell = None;
 
             TableBuilder::new(ui)
                 .column(Column::initial(30.))
-                .columns(Column::initial(50.), column_count * 2)
+                .columns(Column::initial(50.), cells.len())
                 .column(Column::remainder())
                 .striped(true)
                 .resizable(true)
@@ -516,127 +2704,45 @@ impl eframe::App for App {
                         ui.heading("i");
                     });
 
-                    for column_index in 0..self.columns.len() {
-                        header.col(|ui| {
-                            ui.horizontal(|ui| {
-                                ui.heading(self.columns[column_index].name.clone());
-                                if ui.button("⚙".to_owned()).clicked() {
-                                    self.popup_status = PopupStatus::ColumnSettings(column_index);
-                                }
-                            });
-                        });
-
+                    for &(x, uncertainty) in &cells {
                         header.col(|ui| {
-                            ui.heading(format!("Δ{}", self.columns[column_index].name));
+                            self.show_column_header(ui, x, uncertainty, &mut column_action);
                         });
                     }
 
                     header.col(|ui| {
                         if ui.button("Add Column").clicked() {
-                            self.add_column("a".to_owned());
-                            self.popup_status = PopupStatus::ColumnSettings(column_count);
+                            self.add_column(self.sheet.new_column_name());
+                            self.popup_status =
+                                PopupStatus::ColumnSettings(self.sheet.columns.len() - 1);
                         }
                     });
                 })
                 .body(|mut body| {
-                    for y in 0..self.grid.len() {
+                    for y in 0..self.sheet.grid.len() {
                         body.row(20., |mut row| {
                             row.col(|ui| {
-                                if y != self.grid.len() - 1 {
-                                    ui.label((y + 1).to_string());
+                                if y != self.sheet.grid.len() - 1 {
+                                    self.show_line_index(ui, y, &mut line_action);
                                 }
                             });
 
-                            for x in 0..column_count {
-                                // Values column
+                            for (position, &(x, uncertainty)) in cells.iter().enumerate() {
                                 row.col(|ui| {
-                                    if self.columns[x].expression.is_empty() {
-                                        let invalid = self.grid[y][x].value.is_nan();
-
-                                        let mut text_edit =
-                                            TextEdit::singleline(&mut self.grid[y][x].raw_value);
-
-                                        if invalid {
-                                            text_edit = text_edit.text_color(Color32::RED);
-                                        }
-
-                                        let input = ui.add(text_edit);
-
-                                        if input.lost_focus() {
-                                            self.grid[y][x].raw_value =
-                                                self.grid[y][x].raw_value.trim().to_owned();
-                                        }
-
-                                        if input.changed() {
-                                            self.grid[y][x].value = self.grid[y][x]
-                                                .raw_value
-                                                .trim()
-                                                .parse::<f64>()
-                                                .unwrap_or(f64::NAN);
-
-                                            self.compute_line_with_uncertainty(y);
-                                        }
-                                    } else if y != self.grid.len() - 1 {
-                                        let value = self.grid[y][x].value;
-
-                                        let mut rich_text = RichText::new(format!(
-                                            "{value:.*}",
-                                            self.columns[x].precision
-                                        ));
-
-                                        if value.is_nan() {
-                                            rich_text = rich_text.color(Color32::RED);
-                                        }
-
-                                        ui.label(rich_text);
+                                    if uncertainty {
+                                        self.show_uncertainty_cell(ui, y, x);
+                                    } else {
+                                        self.show_value_cell(ui, y, x);
                                     }
-                                });
-
-                                // Uncertainty column
-                                row.col(|ui| {
-                                    if self.columns[x].expression.is_empty() {
-                                        let invalid = self.grid[y][x].uncertainty.is_nan();
 
-                                        let mut text_edit = TextEdit::singleline(
-                                            &mut self.grid[y][x].raw_uncertainty,
-                                        );
-
-                                        if invalid {
-                                            text_edit = text_edit.text_color(Color32::RED);
-                                        }
-
-                                        let input = ui.add(text_edit);
-
-                                        if input.lost_focus() {
-                                            self.grid[y][x].raw_uncertainty =
-                                                self.grid[y][x].raw_uncertainty.trim().to_owned();
+                                    if let Some((lines, positions)) = &selection {
+                                        if lines.contains(&y) && positions.contains(&position) {
+                                            ui.painter().rect_filled(
+                                                ui.max_rect(),
+                                                0.,
+                                                ui.visuals().selection.bg_fill.linear_multiply(0.3),
+                                            );
                                         }
-
-                                        if input.changed() {
-                                            if self.grid[y][x].raw_uncertainty.is_empty() {
-                                                self.grid[y][x].uncertainty = 0.;
-                                            } else {
-                                                self.grid[y][x].uncertainty = self.grid[y][x]
-                                                    .raw_uncertainty
-                                                    .trim()
-                                                    .parse::<f64>()
-                                                    .unwrap_or(f64::NAN);
-                                            }
-
-                                            self.compute_line_with_uncertainty(y);
-                                        }
-                                    } else if y != self.grid.len() - 1 {
-                                        let uncertainty = self.grid[y][x].uncertainty;
-
-                                        let mut rich_text = RichText::new(format!(
-                                            "{uncertainty:.*}",
-                                            self.columns[x].precision
-                                        ));
-                                        if uncertainty.is_nan() {
-                                            rich_text = rich_text.color(Color32::RED);
-                                        }
-
-                                        ui.label(rich_text);
                                     }
                                 });
                             }
@@ -644,29 +2750,61 @@ impl eframe::App for App {
                     }
                 });
 
-            self.ensure_empty_line();
+            if ui.input(|i| i.pointer.any_released()) {
+                self.dragged_line = None;
+                self.dragged_column = None;
+            }
+
+            // A single focused cell copies its selected text itself
+            if ui.input(|i| i.events.contains(&Event::Copy))
+                && (selection.is_some() || self.focused_cell.is_none())
+            {
+                if let Some(text) = self.copy_selection() {
+                    ui.output_mut(|output| output.copied_text = text);
+                }
+            }
+
+            if let Some(action) = line_action {
+                self.apply_line_action(action);
+            }
+
+            if let Some(action) = column_action {
+                self.apply_column_action(action);
+            }
+
+            self.sheet.ensure_empty_line();
         });
 
         SidePanel::right("graph_panel").show(ctx, |ui| {
-            let mut box_list: Vec<BoxElem> = Vec::new();
-
-            let mut x_sum = 0.;
-            let mut y_sum = 0.;
+            ui.horizontal(|ui| {
+                if ui.radio(self.histogram_column.is_none(), "Fit").clicked() {
+                    self.histogram_column = None;
+                }
 
-            let mut xx_sum = 0.;
+                if ui
+                    .radio(self.histogram_column.is_some(), "Histogram")
+                    .clicked()
+                    && self.histogram_column.is_none()
+                {
+                    self.histogram_column = Some(0);
+                }
+            });
 
-            let mut xy_sum = 0.;
+            if let Some(column_index) = self.histogram_column {
+                self.show_histogram(ui, column_index);
+                return;
+            }
 
-            let mut n = 0.0;
+            let mut box_list: Vec<BoxElem> = Vec::new();
 
             let mut min_x = 0f64;
             let mut max_x = 0f64;
 
-            for line in 0..self.grid.len() {
-                let x = self.grid[line][1].value;
-                let y = self.grid[line][0].value;
-                let uncertainty_x = self.grid[line][1].uncertainty;
-                let uncertainty_y = self.grid[line][0].uncertainty;
+            for line in 0..self.sheet.grid.len() {
+                let x = self.sheet.grid[line][1].value;
+                let y = self.sheet.grid[line][0].value;
+                let uncertainty_x = self.sheet.grid[line][1].uncertainty;
+                let uncertainty_y = self.sheet.grid[line][0].uncertainty;
 
                 if x.is_nan() || y.is_nan() {
                     continue;
@@ -710,40 +2848,29 @@ impl eframe::App for App {
                         .whisker_width(uncertainty_y * 2.),
                     );
                 }
-
-                x_sum += x;
-                y_sum += y;
-
-                xx_sum += x * x;
-
-                xy_sum += x * y;
-
-                n += 1.0;
             }
 
-            let line = if self.linear_regression {
-                let slope = xy_sum / xx_sum;
-
-                ui.label(format!("Slope : {slope}"));
-
-                Line::new(PlotPoints::from_explicit_callback(
-                    move |x| slope * x,
-                    min_x..max_x,
-                    1024,
-                ))
-            } else {
-                let slope = (n * xy_sum - x_sum * y_sum) / (n * xx_sum - x_sum.powi(2));
-                let height = (y_sum - slope * x_sum) / n;
+            let (slope, height) = (self.sheet.fit.slope(), self.sheet.fit.height());
 
-                ui.label(format!("Slope : {slope}"));
-                ui.label(format!("Height : {height}"));
+            ui.label(format!(
+                "Slope : {} ± {}",
+                self.sheet.format_number(slope),
+                self.sheet.format_number(self.sheet.fit.uncertainties[0])
+            ));
 
-                Line::new(PlotPoints::from_explicit_callback(
-                    move |x| slope * x + height,
-                    min_x..max_x,
-                    1024,
-                ))
+            if !self.sheet.linear_regression {
+                ui.label(format!(
+                    "Height : {} ± {}",
+                    self.sheet.format_number(height),
+                    self.sheet.format_number(self.sheet.fit.uncertainties[1])
+                ));
             }
+
+            let line = Line::new(PlotPoints::from_explicit_callback(
+                move |x| slope.mul_add(x, height),
+                min_x..max_x,
+                1024,
+            ))
             .width(2.)
             .highlight(false)
             .color(Color32::from_rgb(255, 63, 63));
@@ -756,11 +2883,62 @@ impl eframe::App for App {
                 box_plot.horizontal()
             };
 
-            Plot::new("my_plot").show(ui, |plot_ui| {
-                plot_ui.box_plot(box_plot);
-                plot_ui.line(line);
-            });
+            let point = |line_n: usize| {
+                [
+                    self.sheet.grid[line_n][1].value,
+                    self.sheet.grid[line_n][0].value,
+                ]
+            };
+
+            let excluded_points = Points::new(
+                (0..self.sheet.grid.len())
+                    .filter(|line_n| self.sheet.line_excluded(*line_n))
+                    .map(point)
+                    .collect::<Vec<[f64; 2]>>(),
+            )
+            .shape(MarkerShape::Cross)
+            .radius(6.)
+            .color(Color32::GRAY);
+
+            let outlier_points = Points::new(
+                self.outlier_lines
+                    .iter()
+                    .copied()
+                    .filter(|line_n| *line_n < self.sheet.grid.len())
+                    .map(point)
+                    .collect::<Vec<[f64; 2]>>(),
+            )
+            .shape(MarkerShape::Circle)
+            .filled(false)
+            .radius(8.)
+            .color(OUTLIER_COLOR);
+
+            let x_unit = self.sheet.column_unit_label(1);
+            let y_unit = self.sheet.column_unit_label(0);
+            let x_name = self.sheet.columns[1].name.clone();
+            let y_name = self.sheet.columns[0].name.clone();
+
+            let separator = self.sheet.decimal_separator;
+
+            Plot::new("my_plot")
+                .x_axis_formatter(move |x, range| axis_label(x, range, separator, &x_unit))
+                .y_axis_formatter(move |y, range| axis_label(y, range, separator, &y_unit))
+                .label_formatter(move |_, point| {
+                    format!(
+                        "{x_name} = {}\n{y_name} = {}",
+                        separator.localize(point.x.to_string()),
+                        separator.localize(point.y.to_string())
+                    )
+                })
+                .show(ui, |plot_ui| {
+                    plot_ui.box_plot(box_plot);
+                    plot_ui.line(line);
+                    plot_ui.points(excluded_points);
+                    plot_ui.points(outlier_points);
+                });
         });
+
+        self.record_history(ctx);
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use auto_graph::csv::{parse_title, read_csv, write_csv};
+use auto_graph::fit::Fit;
+use auto_graph::format::{format_measure, NumberFormat};
+use auto_graph::number::DecimalSeparator;
+use auto_graph::project::{link_sheets, load_sheet_names, sheet_prefix};
+use auto_graph::sheet::Sheet;
+use auto_graph::svg::plot;
+
+const USAGE: &str = "usage: auto_graph process <data.csv | project.ron> \
+                     [--expr \"name (unit) = expression\"]... [--fit linear | affine | none] \
+                     [--x column] [--y column] [--sheet name] [--decimal auto | point | comma] \
+                     [--output results.csv] [--plot plot.svg]";
+
+/// Regression asked with `--fit`, a linear fit goes through the origin
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Regression {
+    Linear,
+    Affine,
+    None,
+}
+
+struct Options {
+    input: String,
+    /// Computed columns written `name (unit) = expression`, a column can read the ones defined
+    /// before it
+    expressions: Vec<String>,
+    regression: Option<Regression>,
+    x: Option<String>,
+    y: Option<String>,
+    /// Sheet of a project, needed when it has several
+    sheet: Option<String>,
+    decimal_separator: Option<DecimalSeparator>,
+    output: Option<String>,
+    plot: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut input = None;
+        let mut options = Self {
+            input: String::new(),
+            expressions: Vec::new(),
+            regression: None,
+            x: None,
+            y: None,
+            sheet: None,
+            decimal_separator: None,
+            output: None,
+            plot: None,
+        };
+
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+
+            match arg.as_str() {
+                "--expr" => options.expressions.push(value()?),
+                "--fit" => {
+                    options.regression = Some(match value()?.as_str() {
+                        "linear" => Regression::Linear,
+                        "affine" => Regression::Affine,
+                        "none" => Regression::None,
+                        fit => {
+                            return Err(format!(
+                                "unknown fit {fit}, expected linear, affine or none"
+                            ))
+                        }
+                    });
+                }
+                "--x" => options.x = Some(value()?),
+                "--y" => options.y = Some(value()?),
+                "--sheet" => options.sheet = Some(value()?),
+                "--decimal" => {
+                    let key = value()?;
+                    options.decimal_separator =
+                        Some(DecimalSeparator::from_key(&key).ok_or_else(|| {
+                            format!(
+                                "unknown decimal separator {key}, expected auto, point or comma"
+                            )
+                        })?);
+                }
+                "--output" => options.output = Some(value()?),
+                "--plot" => options.plot = Some(value()?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ if input.is_none() => input = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {arg}")),
+            }
+        }
+
+        options.input = input.ok_or("no input file")?;
+
+        Ok(options)
+    }
+}
+
+/// Reads the sheet `sheet_name` of a project saved by the app, a RON map from the storage keys to
+/// their text, with the values it reads from the other sheets, the name can be omitted if the
+/// project has a single sheet
+fn load_project(text: &str, sheet_name: Option<&str>) -> Result<Sheet, String> {
+    let storage: HashMap<String, String> =
+        ron::from_str(text).map_err(|error| format!("invalid project: {error}"))?;
+
+    let names = load_sheet_names(&|key| storage.get(key).cloned());
+
+    let index = match sheet_name {
+        Some(sheet_name) => names
+            .iter()
+            .position(|name| name == sheet_name)
+            .ok_or_else(|| {
+                format!(
+                    "there is no sheet {sheet_name}, the sheets are {}",
+                    names.join(", ")
+                )
+            })?,
+        None if names.len() > 1 => {
+            return Err(format!(
+                "the project has several sheets, choose one with --sheet: {}",
+                names.join(", ")
+            ))
+        }
+        None => 0,
+    };
+
+    let mut sheets: Vec<Sheet> = (0..names.len())
+        .map(|sheet_n| {
+            let prefix = sheet_prefix(sheet_n);
+            Sheet::load(&|key| storage.get(&format!("{prefix}{key}")).cloned())
+        })
+        .collect();
+
+    link_sheets(&mut sheets.iter_mut().collect::<Vec<&mut Sheet>>(), &names);
+
+    Ok(sheets.swap_remove(index))
+}
+
+/// Inserts the computed columns before the others, each one can read the columns on its right
+fn add_expressions(sheet: &mut Sheet, expressions: &[String]) -> Result<(), String> {
+    for definition in expressions {
+        let (title, expression) = definition
+            .split_once('=')
+            .ok_or_else(|| format!("{definition} is not written name = expression"))?;
+        let (name, unit) = parse_title(title);
+
+        if name.is_empty() {
+            return Err(format!("{definition} has no column name"));
+        }
+
+        if sheet.columns.iter().any(|column| column.name == name) {
+            return Err(format!("{name} is already a column"));
+        }
+
+        sheet.insert_column(0, name);
+        let column = &mut sheet.columns[0];
+        expression.trim().clone_into(&mut column.expression);
+        column.unit = unit;
+        column.update_unit_factor();
+    }
+
+    Ok(())
+}
+
+/// Messages for the invalid expressions and the cells without a finite value or uncertainty
+fn diagnostics(sheet: &Sheet) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut invalid_columns = Vec::new();
+
+    for (column_n, column) in sheet.columns.iter().enumerate() {
+        if let Some(error) = sheet.column_name_error(column_n) {
+            messages.push(format!("{}: {error}", column.name));
+        }
+
+        if let Some(error) = sheet.expression_error(column_n) {
+            messages.push(format!("{}: {error}", column.name));
+            invalid_columns.push(column_n);
+        } else if let Err(error) = sheet.column_dimension(column_n) {
+            messages.push(format!("{}: {error}", column.name));
+        }
+    }
+
+    for (line_n, line) in sheet.grid[..sheet.filled_line_count()].iter().enumerate() {
+        for (column_n, cell) in line.iter().enumerate() {
+            let measured = sheet.columns[column_n].expression.is_empty();
+
+            // Empty measured cells are missing measures, not errors, and the cells of an invalid
+            // expression are already reported
+            if (measured && cell.raw_value.trim().is_empty()) || invalid_columns.contains(&column_n)
+            {
+                continue;
+            }
+
+            let error = if cell.value.is_nan() {
+                sheet.cell_error(line_n, column_n, false)
+            } else if cell.value.is_infinite() {
+                "the value is infinite".to_owned()
+            } else if cell.uncertainty.is_nan() {
+                sheet.cell_error(line_n, column_n, true)
+            } else if cell.uncertainty.is_infinite() {
+                "the uncertainty is infinite".to_owned()
+            } else {
+                continue;
+            };
+
+            messages.push(format!(
+                "line {}, {}: {error}",
+                line_n + 1,
+                sheet.columns[column_n].name
+            ));
+        }
+    }
+
+    messages
+}
+
+fn column_index(sheet: &Sheet, name: &str) -> Result<usize, String> {
+    sheet
+        .columns
+        .iter()
+        .position(|column| column.name == name)
+        .ok_or_else(|| format!("there is no column {name}"))
+}
+
+/// Prints the fitted parameters, named like in expressions, with the significant figures of
+/// their uncertainty
+fn print_fit(fit: &Fit, name: &str, through_origin: bool, x: &str, y: &str) {
+    let measure = |value: f64, uncertainty: f64| {
+        let (value, uncertainty) = format_measure(value, uncertainty, NumberFormat::Significant, 3);
+        format!("{value} ± {uncertainty}")
+    };
+
+    if through_origin {
+        println!("{y} = {name}.slope * {x}");
+    } else {
+        println!("{y} = {name}.slope * {x} + {name}.height");
+    }
+
+    println!(
+        "{name}.slope = {}",
+        measure(fit.slope(), fit.uncertainties[0])
+    );
+
+    if !through_origin {
+        println!(
+            "{name}.height = {}",
+            measure(fit.height(), fit.uncertainties[1])
+        );
+    }
+}
+
+/// Sheet read from the input `text` with the computed columns, its fit is the one of the y
+/// column against the x column so that expressions read the printed parameters, returns it
+/// with the x and y columns and the regression
+fn load_sheet(
+    options: &Options,
+    text: &str,
+    project: bool,
+) -> Result<(Sheet, usize, usize, Regression), String> {
+    if options.sheet.is_some() && !project {
+        return Err("--sheet only applies to projects".to_owned());
+    }
+
+    let mut sheet = if project {
+        load_project(text, options.sheet.as_deref())?
+    } else {
+        read_csv(text, options.decimal_separator.unwrap_or_default())?
+    };
+
+    if let (true, Some(decimal_separator)) = (project, options.decimal_separator) {
+        sheet.decimal_separator = decimal_separator;
+        sheet.compute_and_parse_all();
+    }
+
+    if sheet.columns.len() + options.expressions.len() < 2 {
+        return Err("at least two columns are needed".to_owned());
+    }
+
+    // By default the first two columns are plotted, like in the app
+    let y_name = options
+        .y
+        .clone()
+        .unwrap_or_else(|| sheet.columns[0].name.clone());
+    let x_name = options.x.clone().unwrap_or_else(|| {
+        sheet
+            .columns
+            .get(1)
+            .map_or_else(String::new, |column| column.name.clone())
+    });
+
+    add_expressions(&mut sheet, &options.expressions)?;
+
+    let x = column_index(&sheet, &x_name)?;
+    let y = column_index(&sheet, &y_name)?;
+
+    let regression = options
+        .regression
+        .unwrap_or(if project && sheet.linear_regression {
+            Regression::Linear
+        } else {
+            Regression::Affine
+        });
+
+    if regression != Regression::None {
+        sheet.linear_regression = regression == Regression::Linear;
+    }
+
+    sheet.fit_columns = [y, x];
+    sheet.compute_all();
+
+    Ok((sheet, x, y, regression))
+}
+
+/// Loads the input, computes the columns and the fit, then prints or writes the results,
+/// returns whether every expression and cell is valid
+fn run(options: &Options) -> Result<bool, String> {
+    let text = fs::read_to_string(&options.input)
+        .map_err(|error| format!("can't read {}: {error}", options.input))?;
+
+    let project = Path::new(&options.input)
+        .extension()
+        .is_some_and(|extension| extension == "ron");
+
+    let (sheet, x, y, regression) = load_sheet(options, &text, project)?;
+    let x_name = &sheet.columns[x].name;
+    let y_name = &sheet.columns[y].name;
+
+    let messages = diagnostics(&sheet);
+    for message in &messages {
+        eprintln!("{message}");
+    }
+
+    let fit = (regression != Regression::None).then_some(sheet.fit);
+    let mut valid = messages.is_empty();
+
+    if let Some(fit) = &fit {
+        if fit
+            .values
+            .iter()
+            .chain(&fit.uncertainties)
+            .all(|value| value.is_finite())
+        {
+            print_fit(
+                fit,
+                sheet.fit_name.trim(),
+                regression == Regression::Linear,
+                x_name,
+                y_name,
+            );
+        } else {
+            eprintln!("the fit can't be computed, it needs more lines with a value of {x_name} and {y_name}");
+            valid = false;
+        }
+    }
+
+    if let Some(output) = &options.output {
+        fs::write(output, write_csv(&sheet))
+            .map_err(|error| format!("can't write {output}: {error}"))?;
+    } else {
+        print!("{}", write_csv(&sheet));
+    }
+
+    if let Some(path) = &options.plot {
+        fs::write(path, plot(&sheet, x, y, fit.as_ref()))
+            .map_err(|error| format!("can't write {path}: {error}"))?;
+    }
+
+    Ok(valid)
+}
+
+/// Runs `auto_graph process` without opening a window, returns the exit code : 0 if everything
+/// is valid, 1 if an expression or a cell is invalid or a file can't be used, 2 for invalid
+/// arguments
+pub fn process(args: &[String]) -> i32 {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {error}\n{USAGE}");
+            return 2;
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(error) => {
+            eprintln!("error: {error}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+        Options::parse(&args).unwrap()
+    }
+
+    #[test]
+    fn expressions_read_the_printed_fit() {
+        let text = "t (s),d (m)\n1,2.1\n2,3.9\n3,6.2\n4,7.8\n";
+        let options = options(&[
+            "data.csv",
+            "--expr",
+            "v (m/s) = fit.slope",
+            "--x",
+            "t",
+            "--y",
+            "d",
+        ]);
+
+        let (sheet, x, y, regression) = load_sheet(&options, text, false).unwrap();
+        assert_eq!(
+            (
+                sheet.columns[x].name.as_str(),
+                sheet.columns[y].name.as_str()
+            ),
+            ("t", "d")
+        );
+        assert!(regression == Regression::Affine);
+
+        let points = [(1., 2.1), (2., 3.9), (3., 6.2), (4., 7.8)];
+        let expected = Fit::new(&points, false);
+        assert!((sheet.fit.slope() - expected.slope()).abs() < 1e-12);
+        assert!((sheet.fit.height() - expected.height()).abs() < 1e-12);
+
+        let v = column_index(&sheet, "v").unwrap();
+        assert!((sheet.grid[0][v].value - expected.slope()).abs() < 1e-12);
+        assert!((sheet.grid[0][v].uncertainty - expected.uncertainties[0]).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::fit::FIT_PARAMETERS;
+use crate::sheet::Sheet;
+use crate::units::{Dimension, Unit};
+
+/// Value of a sheet compared by the compatibility test
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Cell {
+        line: usize,
+        column: usize,
+    },
+    /// Index in `FIT_PARAMETERS`
+    FitParameter(usize),
+    Constant(usize),
+}
+
+/// Value and uncertainty in SI base units with their dimension, if it is known
+pub struct QuantityValue {
+    pub value: f64,
+    pub uncertainty: f64,
+    pub dimension: Option<Dimension>,
+}
+
+impl Sheet {
+    /// Name of a quantity as shown to the user, `None` if it doesn't exist anymore
+    #[must_use]
+    pub fn quantity_name(&self, quantity: Quantity) -> Option<String> {
+        match quantity {
+            Quantity::Cell { line, column } => (line < self.grid.len()
+                && column < self.columns.len())
+            .then(|| format!("{} line {}", self.columns[column].name, line + 1)),
+            Quantity::FitParameter(parameter_n) => FIT_PARAMETERS
+                .get(parameter_n)
+                .map(|parameter| format!("{}.{parameter}", self.fit_name.trim())),
+            Quantity::Constant(constant_n) => self
+                .constants
+                .get(constant_n)
+                .map(|constant| constant.name.clone()),
+        }
+    }
+
+    /// Value of a quantity converted to SI base units, `None` if it doesn't exist anymore
+    #[must_use]
+    pub fn quantity_value(&self, quantity: Quantity) -> Option<QuantityValue> {
+        match quantity {
+            Quantity::Cell { line, column } => {
+                let cell = self.grid.get(line)?.get(column)?;
+                let factor = self.columns[column].unit_factor;
+
+                Some(QuantityValue {
+                    value: cell.value * factor,
+                    uncertainty: cell.uncertainty * factor,
+                    dimension: self.column_dimension(column).ok(),
+                })
+            }
+            Quantity::FitParameter(parameter_n) => {
+                let (_, value, uncertainty) = self.fit_variables().nth(parameter_n)?;
+
+                Some(QuantityValue {
+                    value,
+                    uncertainty,
+                    dimension: self.fit_dimensions()[parameter_n],
+                })
+            }
+            Quantity::Constant(constant_n) => {
+                let constant = self.constants.get(constant_n)?;
+
+                Some(QuantityValue {
+                    value: constant.si_value,
+                    uncertainty: constant.si_uncertainty,
+                    dimension: Unit::parse(&constant.unit).ok().map(|unit| unit.dimension),
+                })
+            }
+        }
+    }
+}
+
+/// Conclusion of the compatibility test for given thresholds on the normalized difference
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Compatible,
+    /// Between the two thresholds, neither clearly compatible nor incompatible
+    Tension,
+    Incompatible,
+}
+
+/// Comparison of two values with their standard uncertainties, assumed independent
+pub struct Compatibility {
+    pub difference: f64,
+    /// `√(Δa² + Δb²)`
+    pub combined_uncertainty: f64,
+    /// `|a - b| / √(Δa² + Δb²)`, infinite for different exact values
+    pub normalized_difference: f64,
+    /// Whether the intervals `[a - Δa, a + Δa]` and `[b - Δb, b + Δb]` overlap
+    pub overlap: bool,
+}
+
+impl Compatibility {
+    #[must_use]
+    pub fn new((a, a_uncertainty): (f64, f64), (b, b_uncertainty): (f64, f64)) -> Self {
+        let difference = (a - b).abs();
+        let combined_uncertainty = a_uncertainty.hypot(b_uncertainty);
+
+        Self {
+            difference,
+            combined_uncertainty,
+            normalized_difference: if difference == 0. {
+                0.
+            } else {
+                difference / combined_uncertainty
+            },
+            overlap: difference <= a_uncertainty.abs() + b_uncertainty.abs(),
+        }
+    }
+
+    /// Compatible below `compatible` standard uncertainties of the difference and incompatible
+    /// above `incompatible`
+    #[must_use]
+    pub fn verdict(&self, compatible: f64, incompatible: f64) -> Verdict {
+        if self.normalized_difference <= compatible {
+            Verdict::Compatible
+        } else if self.normalized_difference <= incompatible {
+            Verdict::Tension
+        } else {
+            Verdict::Incompatible
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sheet::Constant;
+
+    #[test]
+    fn measured_g_against_the_standard_value() {
+        // 9.70 ± 0.03 against 9.81 ± 0.01 is 3.5 combined uncertainties away
+        let compatibility = Compatibility::new((9.70, 0.03), (9.81, 0.01));
+
+        assert!((compatibility.normalized_difference - 0.11 / 0.001f64.sqrt()).abs() < 1e-9);
+        assert!(!compatibility.overlap);
+        assert_eq!(compatibility.verdict(2., 3.), Verdict::Incompatible);
+        assert_eq!(compatibility.verdict(2., 4.), Verdict::Tension);
+    }
+
+    #[test]
+    fn overlapping_intervals() {
+        let compatibility = Compatibility::new((1., 0.5), (1.8, 0.4));
+
+        assert!(compatibility.overlap);
+        assert_eq!(compatibility.verdict(2., 3.), Verdict::Compatible);
+    }
+
+    #[test]
+    fn exact_values() {
+        assert_eq!(
+            Compatibility::new((2., 0.), (2., 0.)).verdict(2., 3.),
+            Verdict::Compatible
+        );
+        assert_eq!(
+            Compatibility::new((2., 0.), (3., 0.)).verdict(2., 3.),
+            Verdict::Incompatible
+        );
+    }
+
+    #[test]
+    fn quantities_are_compared_in_si_units() {
+        let mut sheet = Sheet::new();
+        sheet.add_column("a".to_owned());
+        sheet.add_column("t".to_owned());
+        sheet.columns[0].unit = "cm/s²".to_owned();
+        sheet.columns[0].update_unit_factor();
+        sheet.add_line();
+        sheet.grid[0][0].raw_value = "981".to_owned();
+        sheet.grid[0][0].raw_uncertainty = "2".to_owned();
+        sheet
+            .constants
+            .push(Constant::new("g", "9.81", "0.01", "m/s²"));
+        sheet.compute_and_parse_all();
+
+        let cell = sheet
+            .quantity_value(Quantity::Cell { line: 0, column: 0 })
+            .unwrap();
+        let constant = sheet.quantity_value(Quantity::Constant(0)).unwrap();
+
+        assert!((cell.value - 9.81).abs() < 1e-12 && (cell.uncertainty - 0.02).abs() < 1e-12);
+        assert_eq!(cell.dimension, constant.dimension);
+        assert!(sheet.quantity_value(Quantity::Constant(1)).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::number::DecimalSeparator;
+use crate::sheet::Sheet;
+
+/// Splits a line of a CSV file, fields can be quoted with `"` and quotes doubled inside them
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field);
+    fields
+}
+
+/// Quotes a field if it contains the delimiter or a quote
+fn quote_field(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Name and unit of a column written `name (unit)`, the unit is empty if there are no
+/// parentheses
+#[must_use]
+pub fn parse_title(title: &str) -> (String, String) {
+    let title = title.trim();
+
+    title
+        .strip_suffix(')')
+        .and_then(|title| title.rsplit_once('('))
+        .map_or_else(
+            || (title.to_owned(), String::new()),
+            |(name, unit)| (name.trim().to_owned(), unit.trim().to_owned()),
+        )
+}
+
+/// Reads a table whose first line gives the columns as `name` or `name (unit)`, a column named
+/// `Δname` holds the uncertainties of the column `name`
+///
+/// The fields are separated by tabs if the header has one, otherwise by semicolons if it has
+/// one, otherwise by commas
+///
+/// The numbers are read with `decimal_separator`, with the automatic one a file separated by
+/// commas can only use decimal points
+///
+/// # Errors
+///
+/// Fails if the file is empty, if a column has no name or the name of another one, or if an
+/// uncertainty column comes before the column of its values
+pub fn read_csv(text: &str, decimal_separator: DecimalSeparator) -> Result<Sheet, String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or("the file is empty")?;
+
+    let delimiter = ['\t', ';', ',']
+        .into_iter()
+        .find(|delimiter| header.contains(*delimiter))
+        .unwrap_or(',');
+
+    let mut sheet = Sheet::new();
+    sheet.decimal_separator = match decimal_separator {
+        DecimalSeparator::Auto if delimiter == ',' => DecimalSeparator::Point,
+        decimal_separator => decimal_separator,
+    };
+    // Column and whether it is the uncertainty of each field
+    let mut fields = Vec::new();
+
+    for title in split_fields(header, delimiter) {
+        let (name, unit) = parse_title(&title);
+
+        if let Some(name) = name.strip_prefix('Δ') {
+            let column_n = sheet
+                .columns
+                .iter()
+                .position(|column| column.name == name.trim())
+                .ok_or_else(|| format!("{title} comes before a column named {}", name.trim()))?;
+
+            fields.push((column_n, true));
+        } else {
+            if name.is_empty() {
+                return Err("a column has no name".to_owned());
+            }
+
+            if sheet.columns.iter().any(|column| column.name == name) {
+                return Err(format!("{name} is the name of two columns"));
+            }
+
+            let column_n = sheet.columns.len();
+            fields.push((column_n, false));
+            sheet.add_column(name);
+
+            let column = &mut sheet.columns[column_n];
+            column.unit = unit;
+            column.update_unit_factor();
+        }
+    }
+
+    for (line_n, line) in lines.enumerate() {
+        sheet.add_line();
+
+        for (field, &(column_n, uncertainty)) in split_fields(line, delimiter).iter().zip(&fields) {
+            let cell = &mut sheet.grid[line_n][column_n];
+
+            if uncertainty {
+                field.trim().clone_into(&mut cell.raw_uncertainty);
+            } else {
+                field.trim().clone_into(&mut cell.raw_value);
+            }
+        }
+    }
+
+    sheet.ensure_empty_line();
+    sheet.compute_and_parse_all();
+
+    Ok(sheet)
+}
+
+/// Writes the values and uncertainties of every column in their unit, followed by a column
+/// `Δname` for the uncertainties, values which can't be computed or are infinite are left empty
+///
+/// Numbers are formatted like in the table, with the format and precision of their column and
+/// the decimal separator of the sheet, the fields are separated by semicolons if it is a comma
+#[must_use]
+pub fn write_csv(sheet: &Sheet) -> String {
+    let measure = |column_n: usize, value: f64, uncertainty: f64| {
+        if value.is_finite() {
+            sheet.columns[column_n]
+                .format_measure(value, uncertainty, sheet.decimal_separator)
+                .into()
+        } else {
+            [String::new(), String::new()]
+        }
+    };
+
+    let delimiter = if sheet.decimal_separator == DecimalSeparator::Comma {
+        ';'
+    } else {
+        ','
+    };
+
+    let mut text = (0..sheet.columns.len())
+        .flat_map(|column_n| {
+            [
+                quote_field(&sheet.column_title(column_n), delimiter),
+                quote_field(&format!("Δ{}", sheet.columns[column_n].name), delimiter),
+            ]
+        })
+        .collect::<Vec<String>>()
+        .join(&delimiter.to_string());
+    text.push('\n');
+
+    // Computed columns are written on the lines of the measures
+    for line in &sheet.grid[..sheet.filled_line_count()] {
+        let fields: Vec<String> = line
+            .iter()
+            .enumerate()
+            .flat_map(|(column_n, cell)| measure(column_n, cell.value, cell.uncertainty))
+            .collect();
+
+        text.push_str(&fields.join(&delimiter.to_string()));
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_commas_are_read_from_semicolon_separated_files() {
+        let sheet = read_csv("t (s);Δt;d\n1,5;0,1;2\n", DecimalSeparator::Auto).unwrap();
+
+        assert!((sheet.grid[0][0].value - 1.5).abs() < 1e-12);
+        assert!((sheet.grid[0][0].uncertainty - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn comma_separated_files_use_decimal_points() {
+        let sheet = read_csv("a,b\n\"1,5\",2.5\n", DecimalSeparator::Auto).unwrap();
+
+        assert!(sheet.decimal_separator == DecimalSeparator::Point);
+        // A comma alone is ambiguous with decimal points
+        assert!(sheet.grid[0][0].value.is_nan());
+        assert!((sheet.grid[0][1].value - 2.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn decimal_commas_are_written_with_semicolons() {
+        let sheet = read_csv("t (s);Δt;d\n1,5;0,25;2\n", DecimalSeparator::Comma).unwrap();
+
+        assert_eq!(
+            write_csv(&sheet),
+            "t (s);Δt;d;Δd\n1,500;0,250;2,000;0,000\n"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use evalexpr::{EvalexprError, Value, ValueType};
+
+/// Functions of evalexpr proposed by the completion of expressions
+pub const BUILTIN_FUNCTIONS: [&str; 25] = [
+    "math::sqrt",
+    "math::cbrt",
+    "math::pow",
+    "math::hypot",
+    "math::exp",
+    "math::exp2",
+    "math::ln",
+    "math::log",
+    "math::log2",
+    "math::log10",
+    "math::sin",
+    "math::cos",
+    "math::tan",
+    "math::asin",
+    "math::acos",
+    "math::atan",
+    "math::atan2",
+    "math::sinh",
+    "math::cosh",
+    "math::tanh",
+    "floor",
+    "round",
+    "ceil",
+    "if",
+    "math::is_nan",
+];
+
+/// Functions reading other lines of a column, proposed by the completion of expressions
+pub const COLUMN_FUNCTIONS: [&str; 7] = ["prev", "next", "mean", "sum", "min", "max", "stddev"];
+
+const fn type_name(value_type: ValueType) -> &'static str {
+    match value_type {
+        ValueType::String => "a text",
+        ValueType::Float | ValueType::Int => "a number",
+        ValueType::Boolean => "a boolean",
+        ValueType::Tuple => "a tuple",
+        ValueType::Empty => "nothing",
+    }
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    type_name(value.into())
+}
+
+/// Position, counted in characters from 1, of the first parenthesis without a match, opening if
+/// `opening` is set and closing otherwise
+fn unmatched_parenthesis(expression: &str, opening: bool) -> Option<usize> {
+    let mut open = Vec::new();
+
+    for (n, c) in expression.chars().enumerate() {
+        match c {
+            '(' => open.push(n),
+            ')' if open.pop().is_none() && !opening => return Some(n + 1),
+            _ => {}
+        }
+    }
+
+    open.first().filter(|_| opening).map(|n| n + 1)
+}
+
+/// Message explaining why an expression can't be parsed or evaluated, written for the user
+#[must_use]
+pub fn error_message(expression: &str, error: &EvalexprError) -> String {
+    match error {
+        EvalexprError::VariableIdentifierNotFound(name) => format!("unknown variable {name}"),
+        EvalexprError::FunctionIdentifierNotFound(name) => format!("unknown function {name}"),
+        EvalexprError::WrongFunctionArgumentAmount { expected, actual } => format!(
+            "a function takes {expected} argument{} but is given {actual}",
+            if *expected == 1 { "" } else { "s" }
+        ),
+        EvalexprError::WrongOperatorArgumentAmount { .. } => {
+            "an operator is missing a value".to_owned()
+        }
+        EvalexprError::ExpectedNumber { actual }
+        | EvalexprError::ExpectedNumberOrString { actual }
+        | EvalexprError::ExpectedFloat { actual }
+        | EvalexprError::ExpectedInt { actual } => format!(
+            "type mismatch: expected a number, got {} ({actual})",
+            value_type_name(actual)
+        ),
+        EvalexprError::ExpectedBoolean { actual } => format!(
+            "type mismatch: expected a boolean, got {} ({actual})",
+            value_type_name(actual)
+        ),
+        EvalexprError::TypeError { actual, .. } => {
+            format!(
+                "type mismatch: unexpected {} ({actual})",
+                value_type_name(actual)
+            )
+        }
+        EvalexprError::WrongTypeCombination { operator, actual } => format!(
+            "type mismatch: {operator} can't be applied to {}",
+            actual
+                .iter()
+                .map(|value_type| type_name(*value_type))
+                .collect::<Vec<_>>()
+                .join(" and ")
+        ),
+        EvalexprError::UnmatchedLBrace => unmatched_parenthesis(expression, true).map_or_else(
+            || "unclosed parenthesis".to_owned(),
+            |n| format!("parenthesis at character {n} is never closed"),
+        ),
+        EvalexprError::UnmatchedRBrace => unmatched_parenthesis(expression, false).map_or_else(
+            || "unmatched closing parenthesis".to_owned(),
+            |n| format!("parenthesis at character {n} closes nothing"),
+        ),
+        EvalexprError::MissingOperatorOutsideOfBrace => {
+            "missing operator next to a parenthesis, write 2 * (x) instead of 2(x)".to_owned()
+        }
+        EvalexprError::UnmatchedPartialToken { first, .. } => {
+            let token = first.to_string();
+            expression.find(&token).map_or_else(
+                || format!("unexpected {token}"),
+                |index| {
+                    let n = expression[..index].chars().count() + 1;
+                    format!("unexpected {token} at character {n}")
+                },
+            )
+        }
+        error => error.to_string(),
+    }
+}
+
+/// Index, counted in characters, where the identifier ending at the cursor starts
+#[must_use]
+pub fn identifier_start(text: &str, cursor: usize) -> usize {
+    let before: Vec<char> = text.chars().take(cursor).collect();
+
+    before
+        .iter()
+        .rposition(|c| !(c.is_alphanumeric() || matches!(c, '_' | '.' | ':')))
+        .map_or(0, |n| n + 1)
+}
+
+/// Names starting with `prefix`, without duplicates and in alphabetical order
+pub fn completions<'a>(prefix: &str, names: &'a [String]) -> Vec<&'a str> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+
+    let mut completions: Vec<&str> = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| name.starts_with(prefix) && *name != prefix)
+        .collect();
+
+    completions.sort_unstable();
+    completions.dedup();
+    completions
+}
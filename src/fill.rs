@@ -0,0 +1,92 @@
+use evalexpr::{eval_number_with_context, ContextWithMutableVariables, HashMapContext};
+
+use crate::number::DecimalSeparator;
+
+/// How the values of a fill series are generated
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FillKind {
+    /// `start`, `start + step`, `start + 2 step`…
+    Linear,
+    /// `start`, `start * step`, `start * step²`…
+    Geometric,
+    /// `start` on every line
+    Constant,
+    /// Expression of the line number `i`, starting at 1
+    Expression,
+}
+
+/// Settings of the fill action of a measured column, kept as typed by the user
+pub struct Fill {
+    pub kind: FillKind,
+    pub start: String,
+    pub step: String,
+    pub count: usize,
+    pub expression: String,
+}
+
+impl Default for Fill {
+    fn default() -> Self {
+        Self {
+            kind: FillKind::Linear,
+            start: "0".to_owned(),
+            step: "1".to_owned(),
+            count: 10,
+            expression: String::new(),
+        }
+    }
+}
+
+impl Fill {
+    /// Values of the series
+    ///
+    /// # Errors
+    ///
+    /// Gives the name of the first invalid field
+    // Setting a number in a new context can't fail
+    #[allow(clippy::missing_panics_doc)]
+    pub fn values(&self, separator: DecimalSeparator) -> Result<Vec<f64>, &'static str> {
+        let start = || separator.parse(&self.start).ok_or("start");
+        let step = || separator.parse(&self.step).ok_or("step");
+
+        let mut values = Vec::with_capacity(self.count);
+
+        match self.kind {
+            FillKind::Linear => {
+                let (start, step) = (start()?, step()?);
+                let mut k = 0.;
+                for _ in 0..self.count {
+                    values.push(round_noise(step.mul_add(k, start)));
+                    k += 1.;
+                }
+            }
+            FillKind::Geometric => {
+                let step = step()?;
+                let mut value = start()?;
+                for _ in 0..self.count {
+                    values.push(round_noise(value));
+                    value *= step;
+                }
+            }
+            FillKind::Constant => values.resize(self.count, start()?),
+            FillKind::Expression => {
+                let mut context = HashMapContext::new();
+                let mut i = 0.;
+
+                for _ in 0..self.count {
+                    i += 1.;
+                    context.set_value("i".to_owned(), i.into()).unwrap();
+                    let value = eval_number_with_context(&self.expression, &context)
+                        .map_err(|_| "expression")?;
+                    values.push(value);
+                }
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Removes the binary rounding noise of repeated additions (`0.1 * 3 = 0.30000000000000004`)
+fn round_noise(value: f64) -> f64 {
+    format!("{value:.12e}").parse().unwrap_or(value)
+}
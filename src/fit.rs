@@ -0,0 +1,184 @@
+/// Names of the parameters of a fit, as read in expressions after the fit name (`fit.slope`)
+pub const FIT_PARAMETERS: [&str; 2] = ["slope", "height"];
+
+/// Least squares line `y = slope * x + height` with the standard uncertainty of its parameters
+#[derive(Clone, Copy)]
+pub struct Fit {
+    /// Slope and height, in the order of `FIT_PARAMETERS`
+    pub values: [f64; 2],
+    pub uncertainties: [f64; 2],
+    /// Covariance of the slope and the height, the height decreases when the slope increases
+    /// for points on the right of the origin
+    pub covariance: f64,
+}
+
+impl Default for Fit {
+    fn default() -> Self {
+        Self {
+            values: [f64::NAN; 2],
+            uncertainties: [f64::NAN; 2],
+            covariance: f64::NAN,
+        }
+    }
+}
+
+impl Fit {
+    /// Fits the points `(x, y)`, the line goes through the origin if `through_origin` is set,
+    /// points with a NaN coordinate are ignored
+    ///
+    /// The uncertainties come from the scatter of the points around the line
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn new(points: &[(f64, f64)], through_origin: bool) -> Self {
+        let points: Vec<(f64, f64)> = points
+            .iter()
+            .copied()
+            .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+            .collect();
+
+        let n = points.len() as f64;
+
+        let x_sum: f64 = points.iter().map(|(x, _)| x).sum();
+        let y_sum: f64 = points.iter().map(|(_, y)| y).sum();
+        let xx_sum: f64 = points.iter().map(|(x, _)| x * x).sum();
+        let xy_sum: f64 = points.iter().map(|(x, y)| x * y).sum();
+
+        let (slope, height) = if through_origin {
+            (xy_sum / xx_sum, 0.)
+        } else {
+            let slope = n.mul_add(xy_sum, -x_sum * y_sum) / n.mul_add(xx_sum, -x_sum.powi(2));
+            (slope, slope.mul_add(-x_sum, y_sum) / n)
+        };
+
+        let residual_sum: f64 = points
+            .iter()
+            .map(|(x, y)| (y - slope.mul_add(*x, height)).powi(2))
+            .sum();
+
+        let (uncertainties, covariance) = if through_origin {
+            let variance = residual_sum / (n - 1.);
+            ([(variance / xx_sum).sqrt(), 0.], 0.)
+        } else {
+            let variance = residual_sum / (n - 2.);
+            let determinant = n.mul_add(xx_sum, -x_sum.powi(2));
+            (
+                [
+                    (variance * n / determinant).sqrt(),
+                    (variance * xx_sum / determinant).sqrt(),
+                ],
+                -variance * x_sum / determinant,
+            )
+        };
+
+        Self {
+            values: [slope, height],
+            uncertainties,
+            covariance,
+        }
+    }
+
+    #[must_use]
+    pub const fn slope(&self) -> f64 {
+        self.values[0]
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> f64 {
+        self.values[1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance * expected.abs(),
+            "{actual} != {expected}"
+        );
+    }
+
+    /// Norris dataset of the NIST `StRD`, as (x, y)
+    const NORRIS: [(f64, f64); 36] = [
+        (0.2, 0.1),
+        (337.4, 338.8),
+        (118.2, 118.1),
+        (884.6, 888.0),
+        (10.1, 9.2),
+        (226.5, 228.1),
+        (666.3, 668.5),
+        (996.3, 998.5),
+        (448.6, 449.1),
+        (777.0, 778.9),
+        (558.2, 559.2),
+        (0.4, 0.3),
+        (0.6, 0.1),
+        (775.5, 778.1),
+        (666.9, 668.8),
+        (338.0, 339.3),
+        (447.5, 448.9),
+        (11.6, 10.8),
+        (556.0, 557.7),
+        (228.1, 228.3),
+        (995.8, 998.0),
+        (887.6, 888.8),
+        (120.2, 119.6),
+        (0.3, 0.3),
+        (0.3, 0.6),
+        (556.8, 557.6),
+        (339.1, 339.3),
+        (887.2, 888.0),
+        (999.0, 998.5),
+        (779.0, 778.9),
+        (11.1, 10.2),
+        (118.3, 117.6),
+        (229.2, 228.9),
+        (669.1, 668.4),
+        (448.9, 449.2),
+        (0.5, 0.2),
+    ];
+
+    #[test]
+    fn affine_fit_matches_nist_norris() {
+        let fit = Fit::new(&NORRIS, false);
+
+        assert_close(fit.slope(), 1.002_116_818_020_45, 1e-12);
+        assert_close(fit.height(), -0.262_323_073_774_029, 1e-9);
+        assert_close(fit.uncertainties[0], 4.297_968_481_999_37e-4, 1e-9);
+        assert_close(fit.uncertainties[1], 0.232_818_234_301_152, 1e-9);
+
+        // The line pivots around the mean point, cov = -mean(x) * Δslope²
+        let x_mean = NORRIS.iter().map(|(x, _)| x).sum::<f64>() / 36.;
+        assert_close(fit.covariance, -x_mean * fit.uncertainties[0].powi(2), 1e-9);
+    }
+
+    #[test]
+    fn fit_through_origin_matches_nist_noint1() {
+        let points: Vec<(f64, f64)> = (60..=70)
+            .map(|x| (f64::from(x), f64::from(x + 70)))
+            .collect();
+        let fit = Fit::new(&points, true);
+
+        assert_close(fit.slope(), 2.074_380_165_289_26, 1e-12);
+        assert!(fit.height() == 0.);
+        assert!(fit.covariance == 0.);
+        assert_close(fit.uncertainties[0], 1.652_892_561_983_47e-2, 1e-9);
+    }
+
+    #[test]
+    fn fit_through_origin_matches_nist_noint2() {
+        let fit = Fit::new(&[(4., 3.), (5., 4.), (6., 4.)], true);
+
+        assert_close(fit.slope(), 0.727_272_727_272_727, 1e-12);
+        assert_close(fit.uncertainties[0], 4.208_273_180_784_32e-2, 1e-9);
+    }
+
+    #[test]
+    fn points_with_nan_are_ignored() {
+        let fit = Fit::new(&[(1., 2.), (f64::NAN, 5.), (2., 4.), (3., f64::NAN)], false);
+
+        assert_close(fit.slope(), 2., 1e-12);
+        assert!(fit.height().abs() < 1e-12);
+    }
+}
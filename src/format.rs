@@ -0,0 +1,220 @@
+/// How the values and uncertainties of a column are displayed
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// Fixed number of decimals given by the column precision
+    Fixed,
+    /// Uncertainty rounded to 1 or 2 significant figures and value rounded to the same decimal place
+    Significant,
+    /// Mantissa with the column precision as decimals and a power of ten
+    Scientific,
+    /// Like scientific but the power of ten is a multiple of 3
+    Engineering,
+}
+
+impl NumberFormat {
+    #[must_use]
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Fixed => "fixed",
+            Self::Significant => "significant",
+            Self::Scientific => "scientific",
+            Self::Engineering => "engineering",
+        }
+    }
+
+    #[must_use]
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "fixed" => Some(Self::Fixed),
+            "significant" => Some(Self::Significant),
+            "scientific" => Some(Self::Scientific),
+            "engineering" => Some(Self::Engineering),
+            _ => None,
+        }
+    }
+}
+
+/// Values at least this large are shown in scientific notation by the significant format
+const LARGE_VALUE: f64 = 1e6;
+/// Values smaller than this (but not 0) are shown in scientific notation by the significant format
+const SMALL_VALUE: f64 = 1e-3;
+
+/// Power of ten of the leading digit of `x`
+#[allow(clippy::cast_possible_truncation)]
+fn decimal_exponent(x: f64) -> i32 {
+    x.abs().log10().floor() as i32
+}
+
+/// Number of decimals needed to show down to the `10^-places` digit
+fn decimals(places: i32) -> usize {
+    usize::try_from(places).unwrap_or(0)
+}
+
+/// Formats `value / 10^exponent` with `decimals` decimals followed by the exponent if it isn't 0
+fn format_mantissa(value: f64, exponent: i32, decimals: usize) -> String {
+    let mantissa = value / 10f64.powi(exponent);
+
+    if exponent == 0 {
+        format!("{mantissa:.decimals$}")
+    } else {
+        format!("{mantissa:.decimals$}e{exponent}")
+    }
+}
+
+/// Formats a value and its uncertainty, the uncertainty always uses the same notation as the value
+#[must_use]
+pub fn format_measure(
+    value: f64,
+    uncertainty: f64,
+    format: NumberFormat,
+    precision: usize,
+) -> (String, String) {
+    if !value.is_finite() {
+        return (format!("{value}"), format!("{uncertainty:.precision$}"));
+    }
+
+    match format {
+        NumberFormat::Fixed => (
+            format!("{value:.precision$}"),
+            format!("{uncertainty:.precision$}"),
+        ),
+        NumberFormat::Significant => format_significant(value, uncertainty, precision),
+        NumberFormat::Scientific | NumberFormat::Engineering => {
+            let mut exponent = if value == 0. {
+                0
+            } else {
+                decimal_exponent(value)
+            };
+
+            if format == NumberFormat::Engineering {
+                exponent = exponent.div_euclid(3) * 3;
+            }
+
+            (
+                format_mantissa(value, exponent, precision),
+                format_mantissa(uncertainty, exponent, precision),
+            )
+        }
+    }
+}
+
+/// Metrology convention : the uncertainty keeps 2 significant figures if its first digit is
+/// 1 or 2 and 1 otherwise, the value is rounded to the same decimal place
+fn format_significant(value: f64, uncertainty: f64, precision: usize) -> (String, String) {
+    let large_or_small = value.abs() >= LARGE_VALUE || (value != 0. && value.abs() < SMALL_VALUE);
+
+    if !uncertainty.is_finite() || uncertainty <= 0. {
+        // Without uncertainty the precision is used as a number of significant figures
+        let exponent = if value == 0. {
+            0
+        } else {
+            decimal_exponent(value)
+        };
+        let significant = i32::try_from(precision.max(1)).unwrap_or(i32::MAX);
+
+        let value_text = if large_or_small {
+            format_mantissa(value, exponent, decimals(significant - 1))
+        } else {
+            let decimals = decimals(significant - 1 - exponent);
+            format!("{value:.decimals$}")
+        };
+
+        return (value_text, format!("{uncertainty}"));
+    }
+
+    let mut uncertainty_exponent = decimal_exponent(uncertainty);
+    let leading_digit = uncertainty / 10f64.powi(uncertainty_exponent);
+    let significant = if leading_digit < 3. { 2 } else { 1 };
+    let last_place = uncertainty_exponent - (significant - 1);
+
+    // Rounding can carry to the next power of ten (0.96 -> 1.0), the new leading digit is a 1
+    // which keeps 2 significant figures, so the last place doesn't move
+    let rounded = (uncertainty / 10f64.powi(last_place)).round() * 10f64.powi(last_place);
+    if decimal_exponent(rounded) > uncertainty_exponent {
+        uncertainty_exponent += 1;
+    }
+
+    let rounded_value = (value / 10f64.powi(last_place)).round() * 10f64.powi(last_place);
+
+    if large_or_small {
+        let exponent = if rounded_value == 0. {
+            uncertainty_exponent
+        } else {
+            decimal_exponent(rounded_value)
+        };
+        let decimals = decimals(exponent - last_place);
+
+        (
+            format_mantissa(rounded_value, exponent, decimals),
+            format_mantissa(rounded, exponent, decimals),
+        )
+    } else {
+        let decimals = decimals(-last_place);
+
+        (
+            format!("{rounded_value:.decimals$}"),
+            format!("{rounded:.decimals$}"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn significant(value: f64, uncertainty: f64) -> (String, String) {
+        format_measure(value, uncertainty, NumberFormat::Significant, 3)
+    }
+
+    fn pair(value: &str, uncertainty: &str) -> (String, String) {
+        (value.to_owned(), uncertainty.to_owned())
+    }
+
+    #[test]
+    fn leading_one_or_two_keeps_two_figures() {
+        assert_eq!(significant(9.8123, 0.0123), pair("9.812", "0.012"));
+        assert_eq!(significant(9.8123, 0.0271), pair("9.812", "0.027"));
+        assert_eq!(significant(9.8123, 0.0345), pair("9.81", "0.03"));
+        assert_eq!(significant(9.8123, 0.0876), pair("9.81", "0.09"));
+        assert_eq!(significant(1234.5, 56.), pair("1230", "60"));
+    }
+
+    #[test]
+    fn carry_to_a_leading_one_keeps_two_figures() {
+        assert_eq!(significant(12.345, 0.96), pair("12.3", "1.0"));
+        assert_eq!(significant(1.2345, 0.096), pair("1.23", "0.10"));
+    }
+
+    #[test]
+    fn missing_uncertainty_uses_the_precision_as_figures() {
+        assert_eq!(significant(9.8123, 0.).0, "9.81");
+        assert_eq!(significant(9.8123, f64::NAN), pair("9.81", "NaN"));
+        assert_eq!(significant(123_456.7, f64::INFINITY).0, "123457");
+    }
+
+    #[test]
+    fn large_and_small_values_use_powers_of_ten() {
+        assert_eq!(
+            significant(6.022_14e23, 3e18),
+            pair("6.02214e23", "0.00003e23")
+        );
+        assert_eq!(
+            significant(1.234_4e-5, 1.2e-7),
+            pair("1.234e-5", "0.012e-5")
+        );
+    }
+
+    #[test]
+    fn scientific_and_engineering_notations() {
+        let scientific =
+            |value, uncertainty| format_measure(value, uncertainty, NumberFormat::Scientific, 2);
+        let engineering =
+            |value, uncertainty| format_measure(value, uncertainty, NumberFormat::Engineering, 2);
+
+        assert_eq!(scientific(12_345., 67.), pair("1.23e4", "0.01e4"));
+        assert_eq!(scientific(0.5, 0.01), pair("5.00e-1", "0.10e-1"));
+        assert_eq!(engineering(12_345., 67.), pair("12.35e3", "0.07e3"));
+        assert_eq!(engineering(0.004_56, 0.000_12), pair("4.56e-3", "0.12e-3"));
+        assert_eq!(engineering(3.2, 0.1), pair("3.20", "0.10"));
+    }
+}
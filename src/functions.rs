@@ -0,0 +1,155 @@
+use evalexpr::{
+    build_operator_tree, ContextWithMutableFunctions, ContextWithMutableVariables, EvalexprError,
+    Function, HashMapContext, Node, Value,
+};
+
+/// Function of the library, written `name(a, b) = body`
+#[derive(Clone)]
+pub struct UserFunction {
+    pub name: String,
+    pub parameters: Vec<String>,
+    /// Text of the body, kept to find the dimension of the result
+    pub body: String,
+    tree: Node,
+}
+
+/// Whether `name` can be read as a variable or called as a function in expressions
+pub(crate) fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+impl UserFunction {
+    fn parse(definition: &str) -> Result<Self, String> {
+        let (head, body) = definition
+            .split_once('=')
+            .ok_or("expected name(parameters) = body")?;
+
+        let (name, parameters) = head
+            .trim()
+            .strip_suffix(')')
+            .and_then(|head| head.split_once('('))
+            .ok_or("expected name(parameters) before =")?;
+
+        let name = name.trim().to_owned();
+        if !is_identifier(&name) {
+            return Err(format!("{name} is not a valid function name"));
+        }
+
+        let parameters: Vec<String> = parameters
+            .split(',')
+            .map(|parameter| parameter.trim().to_owned())
+            .filter(|parameter| !parameter.is_empty())
+            .collect();
+
+        for (n, parameter) in parameters.iter().enumerate() {
+            if !is_identifier(parameter) {
+                return Err(format!("{parameter} is not a valid parameter name"));
+            }
+            if parameters[..n].contains(parameter) {
+                return Err(format!("{parameter} is used twice"));
+            }
+        }
+
+        let tree = build_operator_tree(body).map_err(|error| error.to_string())?;
+
+        Ok(Self {
+            name,
+            parameters,
+            body: body.trim().to_owned(),
+            tree,
+        })
+    }
+}
+
+/// Parses a library with one definition per line, empty lines and lines starting with `#` are
+/// ignored, invalid lines are returned as errors with their line number
+#[must_use]
+pub fn parse_library(library: &str) -> (Vec<UserFunction>, Vec<String>) {
+    let mut functions: Vec<UserFunction> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line_n, line) in library.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match UserFunction::parse(line) {
+            Ok(function) if functions.iter().any(|f| f.name == function.name) => {
+                errors.push(format!(
+                    "line {}: {} is already defined",
+                    line_n + 1,
+                    function.name
+                ));
+            }
+            Ok(function) => functions.push(function),
+            Err(error) => errors.push(format!("line {}: {error}", line_n + 1)),
+        }
+    }
+
+    (functions, errors)
+}
+
+/// Integers are converted so that `f(1, 2)` doesn't use integer division in the body
+#[allow(clippy::cast_precision_loss)]
+fn float_argument(argument: Value) -> Value {
+    match argument {
+        Value::Int(int) => Value::Float(int as f64),
+        argument => argument,
+    }
+}
+
+/// Context where `pi` and the functions are defined, a body only reads its parameters and `pi`
+/// and can call the functions defined before it
+#[must_use]
+// Functions are only defined once in a new context, which can't fail
+#[allow(clippy::missing_panics_doc)]
+pub fn functions_context(functions: &[UserFunction]) -> HashMapContext {
+    let mut context = HashMapContext::new();
+    context
+        .set_value("pi".to_owned(), std::f64::consts::PI.into())
+        .unwrap();
+
+    for function in functions {
+        let parameters = function.parameters.clone();
+        let tree = function.tree.clone();
+        let definitions = context.clone();
+
+        context
+            .set_function(
+                function.name.clone(),
+                Function::new(move |argument| {
+                    // Several arguments are given as a tuple
+                    let arguments = match (parameters.len(), argument) {
+                        (0, Value::Empty) => Vec::new(),
+                        (1, _) => vec![argument.clone()],
+                        (_, Value::Tuple(arguments)) => arguments.clone(),
+                        _ => vec![argument.clone()],
+                    };
+
+                    if arguments.len() != parameters.len() {
+                        return Err(EvalexprError::WrongFunctionArgumentAmount {
+                            expected: parameters.len(),
+                            actual: arguments.len(),
+                        });
+                    }
+
+                    let mut context = definitions.clone();
+                    for (parameter, argument) in parameters.iter().zip(arguments) {
+                        context.set_value(parameter.clone(), float_argument(argument))?;
+                    }
+
+                    tree.eval_with_context(&context)
+                }),
+            )
+            .unwrap();
+    }
+
+    context
+}
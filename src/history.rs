@@ -0,0 +1,59 @@
+/// Maximum number of states that can be undone
+const MAX_UNDO: usize = 200;
+
+/// Undo and redo stacks around the last recorded state
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    current: T,
+}
+
+impl<T: Clone> History<T> {
+    pub const fn new(current: T) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current,
+        }
+    }
+
+    /// Last recorded state
+    pub const fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// Records a new state, which can't be redone to after an undo anymore
+    pub fn record(&mut self, state: T) {
+        self.undo.push(std::mem::replace(&mut self.current, state));
+        self.redo.clear();
+
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    pub const fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub const fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Goes back to the previous state and returns it
+    pub fn undo(&mut self) -> Option<T> {
+        let previous = self.undo.pop()?;
+        self.redo
+            .push(std::mem::replace(&mut self.current, previous));
+
+        Some(self.current.clone())
+    }
+
+    /// Goes forward to the state before the last undo and returns it
+    pub fn redo(&mut self) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, next));
+
+        Some(self.current.clone())
+    }
+}
@@ -0,0 +1,28 @@
+//! Computation engine of Auto Graph : sheets of measured and computed columns, evaluation of
+//! expressions with units, uncertainty propagation and linear regression, without any GUI
+
+// Lint settings
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![allow(clippy::too_many_lines)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::option_if_let_else)]
+#![allow(clippy::similar_names)]
+#![allow(clippy::struct_excessive_bools)]
+#![allow(clippy::suboptimal_flops)]
+
+pub mod compatibility;
+pub mod csv;
+pub mod diagnostics;
+pub mod fill;
+pub mod fit;
+pub mod format;
+pub mod functions;
+pub mod number;
+pub mod outliers;
+pub mod project;
+pub mod rows;
+pub mod sheet;
+pub mod stats;
+pub mod svg;
+pub mod units;
@@ -11,9 +11,19 @@
 #![allow(clippy::suboptimal_flops)]
 
 mod app;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod history;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `auto_graph process ...` computes a sheet without opening a window
+    if args.first().map(String::as_str) == Some("process") {
+        std::process::exit(cli::process(&args[1..]));
+    }
+
     // Log to stdout (if you run with `RUST_LOG=debug`).
     tracing_subscriber::fmt::init();
 
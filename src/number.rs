@@ -0,0 +1,104 @@
+/// Character used between the integer and the fractional part of numbers
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimalSeparator {
+    /// Both are accepted when typing, numbers are shown with a point
+    #[default]
+    Auto,
+    Point,
+    Comma,
+}
+
+impl DecimalSeparator {
+    #[must_use]
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Point => "point",
+            Self::Comma => "comma",
+        }
+    }
+
+    #[must_use]
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "auto" => Some(Self::Auto),
+            "point" => Some(Self::Point),
+            "comma" => Some(Self::Comma),
+            _ => None,
+        }
+    }
+
+    /// Parses a number typed by the user, digits can be grouped with spaces (`1 234,5`) or with
+    /// the other separator when both are used (`1.234,5` or `1,234.5`)
+    #[must_use]
+    pub fn parse(self, text: &str) -> Option<f64> {
+        let mut number: String = text
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '\u{a0}' | '\u{2009}' | '\u{202f}'))
+            .collect();
+
+        let comma = number.rfind(',');
+        let point = number.rfind('.');
+
+        // With a fixed separator the other one only groups digits, so it can't follow it
+        let decimal_comma = match (self, comma, point) {
+            (Self::Point, Some(_), None) => return None,
+            (Self::Point, Some(comma), Some(point)) if comma > point => return None,
+            (_, None, _) | (Self::Point, Some(_), Some(_)) => false,
+            (Self::Comma, Some(comma), Some(point)) if point > comma => return None,
+            (_, Some(_), None) | (Self::Comma, Some(_), Some(_)) => true,
+            (Self::Auto, Some(comma), Some(point)) => comma > point,
+        };
+
+        if decimal_comma {
+            number = number.replace('.', "").replace(',', ".");
+        } else {
+            number = number.replace(',', "");
+        }
+
+        number.parse::<f64>().ok()
+    }
+
+    /// Uses the separator in a number formatted by Rust
+    #[must_use]
+    pub fn localize(self, text: String) -> String {
+        if self == Self::Comma {
+            text.replace('.', ",")
+        } else {
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_last_separator_is_the_decimal_one() {
+        assert_eq!(DecimalSeparator::Auto.parse("1.234,5"), Some(1234.5));
+        assert_eq!(DecimalSeparator::Auto.parse("1,234.5"), Some(1234.5));
+        assert_eq!(DecimalSeparator::Auto.parse("2,5"), Some(2.5));
+        assert_eq!(DecimalSeparator::Comma.parse("1 234,5"), Some(1234.5));
+    }
+
+    #[test]
+    fn a_comma_alone_is_invalid_with_a_point_separator() {
+        assert_eq!(DecimalSeparator::Point.parse("2,5"), None);
+        assert_eq!(DecimalSeparator::Point.parse("1,234.5"), Some(1234.5));
+        assert_eq!(DecimalSeparator::Point.parse("1.234,5"), None);
+    }
+
+    #[test]
+    fn a_point_only_groups_digits_with_a_comma_separator() {
+        assert_eq!(DecimalSeparator::Comma.parse("1.234,5"), Some(1234.5));
+        assert_eq!(DecimalSeparator::Comma.parse("1,234.5"), None);
+    }
+
+    #[test]
+    fn invalid_numbers_are_rejected() {
+        assert_eq!(DecimalSeparator::Auto.parse(""), None);
+        assert_eq!(DecimalSeparator::Auto.parse("1.2.3x"), None);
+    }
+}
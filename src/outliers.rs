@@ -0,0 +1,286 @@
+use std::f64::consts::{PI, SQRT_2};
+
+/// Complementary error function, with a relative error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let t = 1. / (1. + 0.5 * x.abs());
+    let polynomial = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ]
+    .iter()
+    .rev()
+    .fold(0., |sum, coefficient| sum * t + coefficient);
+
+    let value = t * (-x * x + polynomial).exp();
+
+    if x >= 0. {
+        value
+    } else {
+        2. - value
+    }
+}
+
+/// Logarithm of the gamma function for positive numbers (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    let coefficients = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let series = coefficients
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, coefficient)| {
+            sum + coefficient / (x + 1. + i as f64)
+        });
+    let shifted = x + 5.5;
+
+    (x + 0.5).mul_add(shifted.ln(), -shifted) + (2. * PI).sqrt().ln() + (series / x).ln()
+}
+
+/// Continued fraction of the incomplete beta function, evaluated with Lentz's method
+#[allow(clippy::many_single_char_names)]
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+
+    let mut c = 1.;
+    let mut d = 1. - (a + b) * x / (a + 1.);
+    d = if d.abs() < TINY { 1. / TINY } else { 1. / d };
+    let mut fraction = d;
+
+    for m in 1..300 {
+        let m = f64::from(m);
+
+        for numerator in [
+            m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.)),
+        ] {
+            d = numerator.mul_add(d, 1.);
+            d = if d.abs() < TINY { 1. / TINY } else { 1. / d };
+            c = numerator / c + 1.;
+            c = if c.abs() < TINY { TINY } else { c };
+            fraction *= d * c;
+        }
+
+        if (d * c - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+
+    fraction
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+
+    if x >= 1. {
+        return 1.;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+
+    // The continued fraction converges quickly on this side, the symmetry gives the other one
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1. - front * beta_continued_fraction(b, a, 1. - x) / b
+    }
+}
+
+/// Probability that a normal variable is more than `z` standard deviations above its mean
+fn normal_upper_tail(z: f64) -> f64 {
+    0.5 * erfc(z / SQRT_2)
+}
+
+/// Probability that a Student variable with `freedom` degrees of freedom is above `t`, for
+/// positive `t`
+fn student_upper_tail(t: f64, freedom: f64) -> f64 {
+    0.5 * incomplete_beta(freedom / 2., 0.5, freedom / t.mul_add(t, freedom))
+}
+
+/// Point of `[0, max]` where a decreasing function reaches `target`, by bisection
+fn solve_decreasing(function: impl Fn(f64) -> f64, target: f64, max: f64) -> f64 {
+    let (mut low, mut high) = (0., max);
+
+    for _ in 0..200 {
+        let middle = f64::midpoint(low, high);
+
+        if function(middle) > target {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    f64::midpoint(low, high)
+}
+
+/// Test deciding which values of a sample are outliers, from their distance to the mean in
+/// standard deviations
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutlierTest {
+    /// A value is rejected if fewer than half a value of the sample is expected as far from the
+    /// mean
+    Chauvenet,
+    /// The farthest value is rejected if its distance is above the critical value of the test at
+    /// the given significance level
+    Grubbs(f64),
+    /// Values farther than the given number of standard deviations are rejected
+    ZScore(f64),
+}
+
+/// Result of an outlier test, with the statistics it used
+pub struct OutlierReport {
+    pub mean: f64,
+    /// Sample standard deviation
+    pub deviation: f64,
+    /// Distance of each value to the mean in standard deviations, NaN for missing values
+    pub scores: Vec<f64>,
+    /// Score above which a value is an outlier
+    pub threshold: f64,
+    /// Indices of the outliers in the values
+    pub outliers: Vec<usize>,
+}
+
+impl OutlierTest {
+    /// Applies the test to the values which aren't NaN, the threshold is NaN if there are too few
+    /// values for the test
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn apply(self, values: &[f64]) -> OutlierReport {
+        let valid: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+        let n = valid.len() as f64;
+
+        let mean = valid.iter().sum::<f64>() / n;
+        let deviation = (valid.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.)).sqrt();
+
+        let scores: Vec<f64> = values
+            .iter()
+            .map(|value| (value - mean).abs() / deviation)
+            .collect();
+
+        let threshold = match self {
+            _ if valid.len() < 2 || deviation == 0. => f64::NAN,
+            Self::Chauvenet => solve_decreasing(|z| n * 2. * normal_upper_tail(z), 0.5, 40.),
+            Self::Grubbs(_) if valid.len() < 3 => f64::NAN,
+            Self::Grubbs(significance) => {
+                let freedom = n - 2.;
+                let t = solve_decreasing(
+                    |t| student_upper_tail(t, freedom),
+                    significance / (2. * n),
+                    1e4,
+                );
+
+                (n - 1.) / n.sqrt() * (t * t / t.mul_add(t, freedom)).sqrt()
+            }
+            Self::ZScore(threshold) => threshold,
+        };
+
+        let outliers = if let Self::Grubbs(_) = self {
+            // Only the farthest value is tested
+            scores
+                .iter()
+                .enumerate()
+                .filter(|(_, score)| !score.is_nan())
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .filter(|(_, score)| **score > threshold)
+                .map(|(index, _)| index)
+                .into_iter()
+                .collect()
+        } else {
+            (0..values.len())
+                .filter(|index| scores[*index] > threshold)
+                .collect()
+        };
+
+        OutlierReport {
+            mean,
+            deviation,
+            scores,
+            threshold,
+            outliers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn distribution_tails() {
+        assert_close(normal_upper_tail(1.959_964), 0.025, 1e-7);
+        assert_close(erfc(-1.), 1.842_700_79, 1e-7);
+        // Student quantiles from the usual tables
+        assert_close(student_upper_tail(2.228_139, 10.), 0.025, 1e-7);
+        assert_close(student_upper_tail(12.706_205, 1.), 0.025, 1e-7);
+    }
+
+    #[test]
+    fn chauvenet_threshold() {
+        // For 10 values the rejection probability 1 / 20 is reached at 1.96 σ
+        let values: Vec<f64> = (0..10).map(f64::from).collect();
+        let report = OutlierTest::Chauvenet.apply(&values);
+
+        assert_close(report.threshold, 1.959_964, 1e-5);
+    }
+
+    #[test]
+    fn grubbs_critical_values() {
+        // Two-sided critical values at the 5 % level from the tables of the test
+        for (n, critical) in [(3, 1.1543), (10, 2.2900), (20, 2.7082)] {
+            let values: Vec<f64> = (0..n).map(f64::from).collect();
+            let report = OutlierTest::Grubbs(0.05).apply(&values);
+
+            assert_close(report.threshold, critical, 1e-4);
+        }
+    }
+
+    #[test]
+    fn grubbs_rejects_the_farthest_value() {
+        let values = [9.8, 9.81, 9.79, 9.82, f64::NAN, 9.8, 9.78, 9.81, 10.5];
+        let report = OutlierTest::Grubbs(0.05).apply(&values);
+
+        assert_eq!(report.outliers, [8]);
+        assert!(report.scores[4].is_nan());
+    }
+
+    #[test]
+    fn z_score_threshold() {
+        let report = OutlierTest::ZScore(1.).apply(&[1., 2., 3., 4., 5.]);
+
+        assert_eq!(report.outliers, [0, 4]);
+        assert_close(report.deviation, 2.5f64.sqrt(), 1e-12);
+    }
+
+    #[test]
+    fn too_few_values() {
+        let report = OutlierTest::Grubbs(0.05).apply(&[1., 2.]);
+
+        assert!(report.threshold.is_nan());
+        assert!(report.outliers.is_empty());
+    }
+}
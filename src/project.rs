@@ -0,0 +1,446 @@
+use crate::format::NumberFormat;
+use crate::functions::is_identifier;
+use crate::number::DecimalSeparator;
+use crate::sheet::{Calibration, Constant, External, Sheet, UncertaintyModel};
+
+const LINEAR_REGRESSION_KEY: &str = "linear_regression";
+const DECIMAL_SEPARATOR_KEY: &str = "decimal_separator";
+const FIT_NAME_KEY: &str = "fit_name";
+
+const COLUMN_COUNT_KEY: &str = "column_count";
+const LINE_COUNT_KEY: &str = "line_count";
+const COLUMN_NAME_KEY: &str = "column_name";
+const COLUMN_EXPRESSION_KEY: &str = "column_expression";
+const COLUMN_PRECISION_KEY: &str = "column_precision";
+const COLUMN_FORMAT_KEY: &str = "column_format";
+const COLUMN_UNIT_KEY: &str = "column_unit";
+const COLUMN_HIDDEN_KEY: &str = "column_hidden";
+const COLUMN_UNCERTAINTY_HIDDEN_KEY: &str = "column_uncertainty_hidden";
+const COLUMN_UNCERTAINTY_MODEL_KEY: &str = "column_uncertainty_model";
+const COLUMN_UNCERTAINTY_CONSTANT_KEY: &str = "column_uncertainty_constant";
+const COLUMN_UNCERTAINTY_FORMULA_KEY: &str = "column_uncertainty_formula";
+const COLUMN_CALIBRATION_FIT_KEY: &str = "column_calibration_fit";
+const COLUMN_CALIBRATION_READING_KEY: &str = "column_calibration_reading";
+const FUNCTION_LIBRARY_KEY: &str = "function_library";
+const CONSTANT_COUNT_KEY: &str = "constant_count";
+const CONSTANT_NAME_KEY: &str = "constant_name";
+const CONSTANT_VALUE_KEY: &str = "constant_value";
+const CONSTANT_UNCERTAINTY_KEY: &str = "constant_uncertainty";
+const CONSTANT_UNIT_KEY: &str = "constant_unit";
+const SHEET_COUNT_KEY: &str = "sheet_count";
+const SHEET_NAME_KEY: &str = "sheet_name";
+
+const GRID_VALUE_KEY: &str = "grid_value";
+const GRID_UNCERTAINTY_KEY: &str = "grid_uncertainty";
+const EXCLUDED_LINE_COUNT_KEY: &str = "excluded_line_count";
+const EXCLUDED_LINE_KEY: &str = "excluded_line";
+
+/// Prefix of the keys of the sheet `sheet_n` of a project, the first sheet has none so the
+/// projects saved before there were several sheets keep their keys
+#[must_use]
+pub fn sheet_prefix(sheet_n: usize) -> String {
+    if sheet_n == 0 {
+        String::new()
+    } else {
+        format!("sheet{sheet_n}_")
+    }
+}
+
+/// Names of the sheets of a project, a project without names has one sheet
+pub fn load_sheet_names(get: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+    let count = get(SHEET_COUNT_KEY)
+        .and_then(|text| text.parse().ok())
+        .unwrap_or(1);
+
+    (0..count)
+        .map(|sheet_n| {
+            get(&format!("{SHEET_NAME_KEY}_{sheet_n}"))
+                .unwrap_or_else(|| format!("sheet{}", sheet_n + 1))
+        })
+        .collect()
+}
+
+pub fn save_sheet_names(names: &[&str], set: &mut dyn FnMut(&str, String)) {
+    set(SHEET_COUNT_KEY, names.len().to_string());
+
+    for (sheet_n, name) in names.iter().enumerate() {
+        set(&format!("{SHEET_NAME_KEY}_{sheet_n}"), (*name).to_owned());
+    }
+}
+
+/// Prefix of the values of a sheet in the expressions of the other sheets, its name with `_`
+/// instead of spaces
+#[must_use]
+pub fn sheet_identifier(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+/// Whether expressions can read the values of a sheet called `name`, its identifier can't
+/// start with a digit or hold an operator, nor a point which would mix up with the value names
+#[must_use]
+pub fn is_valid_sheet_name(name: &str) -> bool {
+    let identifier = sheet_identifier(name);
+    is_identifier(&identifier) && !identifier.contains('.')
+}
+
+/// Gives every sheet the constants and fit parameters of the other ones and computes them
+///
+/// Values read through several sheets need a pass per sheet to be up to date, so the sheets are
+/// computed as many times as there are sheets
+pub fn link_sheets(sheets: &mut [&mut Sheet], names: &[String]) {
+    for _ in 0..sheets.len() {
+        let exported: Vec<Vec<External>> = sheets
+            .iter()
+            .zip(names)
+            .map(|(sheet, name)| sheet.exported_values(&sheet_identifier(name)))
+            .collect();
+
+        for (sheet_n, sheet) in sheets.iter_mut().enumerate() {
+            sheet.externals = exported
+                .iter()
+                .enumerate()
+                .filter(|(other_n, _)| *other_n != sheet_n)
+                .flat_map(|(_, values)| values.iter().cloned())
+                .collect();
+
+            sheet.compute_all();
+        }
+    }
+}
+
+impl Sheet {
+    /// Reads a sheet written by [`Sheet::save`], `get` gives the text stored for a key, missing
+    /// or invalid keys keep their default, the values are then computed
+    // Each column is read right after it is added, it can't be missing
+    #[allow(clippy::missing_panics_doc)]
+    pub fn load(get: &dyn Fn(&str) -> Option<String>) -> Self {
+        let mut sheet = Self::new();
+        let count = |key: &str| get(key).and_then(|text| text.parse().ok()).unwrap_or(0);
+
+        if let Some(Ok(linear_regression)) = get(LINEAR_REGRESSION_KEY).map(|text| text.parse()) {
+            sheet.linear_regression = linear_regression;
+        }
+
+        if let Some(fit_name) = get(FIT_NAME_KEY) {
+            sheet.fit_name = fit_name;
+        }
+
+        if let Some(decimal_separator) = get(DECIMAL_SEPARATOR_KEY)
+            .as_deref()
+            .and_then(DecimalSeparator::from_key)
+        {
+            sheet.decimal_separator = decimal_separator;
+        }
+
+        if let Some(function_library) = get(FUNCTION_LIBRARY_KEY) {
+            sheet.function_library = function_library;
+        }
+
+        for constant_n in 0..count(CONSTANT_COUNT_KEY) {
+            let get = |key| get(&format!("{key}_{constant_n}")).unwrap_or_default();
+
+            sheet.constants.push(Constant::new(
+                &get(CONSTANT_NAME_KEY),
+                &get(CONSTANT_VALUE_KEY),
+                &get(CONSTANT_UNCERTAINTY_KEY),
+                &get(CONSTANT_UNIT_KEY),
+            ));
+        }
+
+        let column_count = count(COLUMN_COUNT_KEY);
+
+        // The first two columns are the plotted ones
+        if column_count < 2 {
+            return sheet;
+        }
+
+        for column_n in 0..column_count {
+            let get = |key| get(&format!("{key}_{column_n}"));
+
+            sheet.add_column(get(COLUMN_NAME_KEY).unwrap_or_default());
+            let last = sheet.columns.last_mut().unwrap();
+
+            if let Some(expression) = get(COLUMN_EXPRESSION_KEY) {
+                last.expression = expression;
+            }
+
+            if let Some(Ok(precision)) = get(COLUMN_PRECISION_KEY).map(|text| text.parse()) {
+                last.precision = precision;
+            }
+
+            if let Some(unit) = get(COLUMN_UNIT_KEY) {
+                last.unit = unit;
+                last.update_unit_factor();
+            }
+
+            if let Some(format) = get(COLUMN_FORMAT_KEY)
+                .as_deref()
+                .and_then(NumberFormat::from_key)
+            {
+                last.format = format;
+            }
+
+            if let Some(model) = get(COLUMN_UNCERTAINTY_MODEL_KEY)
+                .as_deref()
+                .and_then(UncertaintyModel::from_key)
+            {
+                last.uncertainty_model = model;
+            }
+
+            if let Some(constant) = get(COLUMN_UNCERTAINTY_CONSTANT_KEY) {
+                last.uncertainty_constant = constant;
+            }
+
+            if let Some(formula) = get(COLUMN_UNCERTAINTY_FORMULA_KEY) {
+                last.uncertainty_formula = formula;
+            }
+
+            if let Some(Ok(hidden)) = get(COLUMN_HIDDEN_KEY).map(|text| text.parse()) {
+                last.hidden = hidden;
+            }
+
+            if let Some(Ok(hidden)) = get(COLUMN_UNCERTAINTY_HIDDEN_KEY).map(|text| text.parse()) {
+                last.uncertainty_hidden = hidden;
+            }
+
+            // Columns without calibration are saved with an empty fit
+            if let (Some(fit), Some(reading)) = (
+                get(COLUMN_CALIBRATION_FIT_KEY).filter(|fit| !fit.is_empty()),
+                get(COLUMN_CALIBRATION_READING_KEY),
+            ) {
+                last.calibration = Some(Calibration { fit, reading });
+            }
+        }
+
+        for line_n in 0..count(LINE_COUNT_KEY) {
+            sheet.add_line();
+
+            for column_n in 0..column_count {
+                let cell = &mut sheet.grid[line_n][column_n];
+                let get = |key| get(&format!("{key}_{line_n}_{column_n}")).unwrap_or_default();
+
+                cell.raw_value = get(GRID_VALUE_KEY);
+                cell.raw_uncertainty = get(GRID_UNCERTAINTY_KEY);
+            }
+        }
+
+        for excluded_n in 0..count(EXCLUDED_LINE_COUNT_KEY) {
+            if let Some(Ok(line_n)) =
+                get(&format!("{EXCLUDED_LINE_KEY}_{excluded_n}")).map(|text| text.parse())
+            {
+                sheet.set_line_excluded(line_n, true);
+            }
+        }
+
+        sheet.compute_and_parse_all();
+
+        sheet
+    }
+
+    /// Writes the settings, constants, columns and cells of the sheet with `set`
+    pub fn save(&self, set: &mut dyn FnMut(&str, String)) {
+        set(LINEAR_REGRESSION_KEY, self.linear_regression.to_string());
+        set(FIT_NAME_KEY, self.fit_name.clone());
+        set(
+            DECIMAL_SEPARATOR_KEY,
+            self.decimal_separator.key().to_owned(),
+        );
+        set(FUNCTION_LIBRARY_KEY, self.function_library.clone());
+
+        set(CONSTANT_COUNT_KEY, self.constants.len().to_string());
+
+        for (constant_n, constant) in self.constants.iter().enumerate() {
+            let mut set = |key, value| set(&format!("{key}_{constant_n}"), value);
+
+            set(CONSTANT_NAME_KEY, constant.name.clone());
+            set(CONSTANT_VALUE_KEY, constant.value.clone());
+            set(CONSTANT_UNCERTAINTY_KEY, constant.uncertainty.clone());
+            set(CONSTANT_UNIT_KEY, constant.unit.clone());
+        }
+
+        set(COLUMN_COUNT_KEY, self.columns.len().to_string());
+        set(LINE_COUNT_KEY, self.grid.len().to_string());
+
+        let excluded_lines: Vec<usize> = (0..self.grid.len())
+            .filter(|line_n| self.line_excluded(*line_n))
+            .collect();
+
+        set(EXCLUDED_LINE_COUNT_KEY, excluded_lines.len().to_string());
+
+        for (excluded_n, line_n) in excluded_lines.iter().enumerate() {
+            set(
+                &format!("{EXCLUDED_LINE_KEY}_{excluded_n}"),
+                line_n.to_string(),
+            );
+        }
+
+        for (column_n, column) in self.columns.iter().enumerate() {
+            let mut set_column = |key, value| set(&format!("{key}_{column_n}"), value);
+
+            set_column(COLUMN_NAME_KEY, column.name.clone());
+            set_column(COLUMN_EXPRESSION_KEY, column.expression.clone());
+            set_column(COLUMN_PRECISION_KEY, column.precision.to_string());
+            set_column(COLUMN_FORMAT_KEY, column.format.key().to_owned());
+            set_column(COLUMN_UNIT_KEY, column.unit.clone());
+            set_column(COLUMN_HIDDEN_KEY, column.hidden.to_string());
+            set_column(
+                COLUMN_UNCERTAINTY_HIDDEN_KEY,
+                column.uncertainty_hidden.to_string(),
+            );
+            set_column(
+                COLUMN_UNCERTAINTY_MODEL_KEY,
+                column.uncertainty_model.key().to_owned(),
+            );
+            set_column(
+                COLUMN_UNCERTAINTY_CONSTANT_KEY,
+                column.uncertainty_constant.clone(),
+            );
+            set_column(
+                COLUMN_UNCERTAINTY_FORMULA_KEY,
+                column.uncertainty_formula.clone(),
+            );
+            set_column(
+                COLUMN_CALIBRATION_FIT_KEY,
+                column
+                    .calibration
+                    .as_ref()
+                    .map_or_else(String::new, |calibration| calibration.fit.clone()),
+            );
+            set_column(
+                COLUMN_CALIBRATION_READING_KEY,
+                column
+                    .calibration
+                    .as_ref()
+                    .map_or_else(String::new, |calibration| calibration.reading.clone()),
+            );
+
+            for (line_n, line) in self.grid.iter().enumerate() {
+                let mut set_cell = |key, value| set(&format!("{key}_{line_n}_{column_n}"), value);
+
+                set_cell(GRID_VALUE_KEY, line[column_n].raw_value.clone());
+                set_cell(GRID_UNCERTAINTY_KEY, line[column_n].raw_uncertainty.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn sheet_identifiers_have_no_spaces() {
+        assert_eq!(sheet_identifier(" calibration  run "), "calibration_run");
+    }
+
+    #[test]
+    fn sheet_names_are_identifiers() {
+        assert!(is_valid_sheet_name("calibration run 2"));
+        assert!(is_valid_sheet_name("_étalonnage"));
+        assert!(!is_valid_sheet_name("run-2"));
+        assert!(!is_valid_sheet_name("2nd run"));
+        assert!(!is_valid_sheet_name("run.2"));
+        assert!(!is_valid_sheet_name(" "));
+    }
+
+    #[test]
+    fn sheets_read_each_other_values() {
+        // y = 2x, fitted through the origin
+        let mut calibration = Sheet::new();
+        calibration.add_column("y".to_owned());
+        calibration.add_column("x".to_owned());
+        for (line_n, (y, x)) in [("2", "1"), ("4", "2"), ("6", "3")].iter().enumerate() {
+            calibration.add_line();
+            calibration.grid[line_n][0].raw_value = (*y).to_owned();
+            calibration.grid[line_n][1].raw_value = (*x).to_owned();
+        }
+        calibration
+            .constants
+            .push(Constant::new("offset", "1", "", ""));
+        calibration.compute_and_parse_all();
+
+        let mut measures = Sheet::new();
+        measures.add_column("z".to_owned());
+        measures.add_column("reading".to_owned());
+        measures.columns[0].expression =
+            "reading / calibration_run.fit.slope + calibration_run.offset".to_owned();
+        measures.add_line();
+        measures.grid[0][1].raw_value = "8".to_owned();
+        measures.compute_and_parse_all();
+
+        let names = ["calibration run".to_owned(), "measures".to_owned()];
+        link_sheets(&mut [&mut calibration, &mut measures], &names);
+
+        assert!((measures.grid[0][0].value - 5.).abs() < 1e-12);
+        assert!(calibration
+            .externals
+            .iter()
+            .any(|value| value.name == "measures.fit.slope"));
+    }
+
+    #[test]
+    fn sheet_names_round_trip() {
+        let mut storage = HashMap::new();
+        save_sheet_names(&["a", "b c"], &mut |key, value| {
+            storage.insert(key.to_owned(), value);
+        });
+
+        assert_eq!(
+            load_sheet_names(&|key| storage.get(key).cloned()),
+            ["a", "b c"]
+        );
+        // A project saved with a single sheet has no names
+        assert_eq!(load_sheet_names(&|_| None), ["sheet1"]);
+        assert_eq!(sheet_prefix(0), "");
+        assert_eq!(sheet_prefix(2), "sheet2_");
+    }
+
+    #[test]
+    fn calibrations_are_saved() {
+        let mut sheet = Sheet::new();
+        sheet.add_column("x".to_owned());
+        sheet.add_column("reading".to_owned());
+        let calibration = Calibration {
+            fit: "calibration.fit".to_owned(),
+            reading: "reading".to_owned(),
+        };
+        sheet.columns[0].expression = calibration.expression();
+        sheet.columns[0].calibration = Some(calibration);
+
+        let mut storage = HashMap::new();
+        sheet.save(&mut |key, value| {
+            storage.insert(key.to_owned(), value);
+        });
+        let loaded = Sheet::load(&|key| storage.get(key).cloned());
+
+        assert!(loaded.columns[0].calibration == sheet.columns[0].calibration);
+        assert!(loaded.columns[1].calibration.is_none());
+    }
+
+    #[test]
+    fn only_excluded_lines_are_saved() {
+        let mut sheet = Sheet::new();
+        sheet.add_column("y".to_owned());
+        sheet.add_column("x".to_owned());
+
+        for line_n in 0..3 {
+            sheet.add_line();
+            sheet.grid[line_n][0].raw_value = line_n.to_string();
+        }
+
+        sheet.set_line_excluded(1, true);
+
+        let mut storage = HashMap::new();
+        sheet.save(&mut |key, value| {
+            storage.insert(key.to_owned(), value);
+        });
+        let loaded = Sheet::load(&|key| storage.get(key).cloned());
+
+        assert_eq!(storage[EXCLUDED_LINE_COUNT_KEY], "1");
+        assert_eq!(
+            (0..3).map(|n| loaded.line_excluded(n)).collect::<Vec<_>>(),
+            [false, true, false]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use evalexpr::{
+    build_operator_tree, ContextWithMutableVariables, EvalexprResult, HashMapContext, Node,
+    Operator,
+};
+
+/// Name of the variable holding the line number, starting at 1
+pub const LINE_VARIABLE: &str = "i";
+
+/// Expression tree where the accesses to other lines and the aggregates are replaced by variables
+pub struct ParsedExpression {
+    pub tree: Node,
+    pub accesses: Vec<RowAccess>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl ParsedExpression {
+    #[must_use]
+    pub const fn reads_other_lines(&self) -> bool {
+        !self.accesses.is_empty() || !self.aggregates.is_empty()
+    }
+}
+
+/// Function computed over every line of a column
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Mean,
+    Sum,
+    Min,
+    Max,
+    StandardDeviation,
+}
+
+impl AggregateFunction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mean" => Some(Self::Mean),
+            "sum" => Some(Self::Sum),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "stddev" => Some(Self::StandardDeviation),
+            _ => None,
+        }
+    }
+
+    /// Name of the function in expressions
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::StandardDeviation => "stddev",
+        }
+    }
+
+    /// Aggregate of the values of a column, lines without value are ignored
+    ///
+    /// Its uncertainty isn't computed here, the cells of the column are perturbed with the ones
+    /// of the line so that the expressions reading both keep their correlation
+    #[allow(clippy::cast_precision_loss)]
+    pub fn compute(self, values: &[f64]) -> f64 {
+        let values: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+
+        if values.is_empty() {
+            return f64::NAN;
+        }
+
+        let n = values.len() as f64;
+        let sum: f64 = values.iter().sum();
+
+        match self {
+            Self::Mean => sum / n,
+            Self::Sum => sum,
+            Self::Min => values.into_iter().fold(f64::INFINITY, f64::min),
+            Self::Max => values.into_iter().fold(f64::NEG_INFINITY, f64::max),
+            Self::StandardDeviation => {
+                if values.len() < 2 {
+                    return f64::NAN;
+                }
+
+                let mean = sum / n;
+
+                (values
+                    .iter()
+                    .map(|value| (value - mean).powi(2))
+                    .sum::<f64>()
+                    / (n - 1.))
+                    .sqrt()
+            }
+        }
+    }
+
+    /// Sign of the change of the aggregate when the cell `value` of a column of mean `mean`
+    /// increases
+    #[must_use]
+    pub fn direction(self, value: f64, mean: f64) -> f64 {
+        if self == Self::StandardDeviation && value < mean {
+            -1.
+        } else {
+            1.
+        }
+    }
+}
+
+/// Aggregate of a column, written `mean(x)`, `sum(x)`, `min(x)`, `max(x)` or `stddev(x)`
+pub struct Aggregate {
+    /// Variable replacing the aggregate in the expression
+    pub variable: String,
+    pub column: usize,
+    pub function: AggregateFunction,
+    /// Value computed before each pass over the lines
+    pub value: f64,
+}
+
+/// Value of a column read on another line, written `prev(x)`, `next(x)` or `x(n)` where `n` is a
+/// line number that can depend on `i`
+pub struct RowAccess {
+    /// Variable replacing the access in the expression
+    pub variable: String,
+    pub column: usize,
+    line: LineIndex,
+}
+
+enum LineIndex {
+    Relative(isize),
+    Expression(Node),
+}
+
+impl RowAccess {
+    /// Index in the grid of the line read while computing the line `line_n`
+    #[must_use]
+    // Setting a number in a new context can't fail
+    #[allow(clippy::missing_panics_doc)]
+    pub fn line(&self, line_n: usize) -> Option<usize> {
+        match &self.line {
+            LineIndex::Relative(offset) => line_n.checked_add_signed(*offset),
+            LineIndex::Expression(index) => {
+                let mut context = HashMapContext::new();
+                context
+                    .set_value(LINE_VARIABLE.to_owned(), line_number(line_n).into())
+                    .unwrap();
+
+                let number = index.eval_number_with_context(&context).ok()?;
+
+                if number.fract() != 0. || number < 1. || number > f64::from(u32::MAX) {
+                    return None;
+                }
+
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                Some(number as usize - 1)
+            }
+        }
+    }
+}
+
+/// Line number shown in the table for the line `line_n` of the grid
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub const fn line_number(line_n: usize) -> f64 {
+    (line_n + 1) as f64
+}
+
+/// Parses an expression and replaces its accesses to other lines by variables, `column` gives
+/// the index of the columns which can be read
+///
+/// # Errors
+///
+/// Fails if the expression can't be parsed
+pub fn parse_row_accesses(
+    expression: &str,
+    column: &dyn Fn(&str) -> Option<usize>,
+) -> EvalexprResult<ParsedExpression> {
+    let mut tree = build_operator_tree(expression)?;
+    let mut accesses = Vec::new();
+    let mut aggregates = Vec::new();
+
+    replace_reads(&mut tree, column, &mut accesses, &mut aggregates);
+
+    Ok(ParsedExpression {
+        tree,
+        accesses,
+        aggregates,
+    })
+}
+
+fn replace_reads(
+    node: &mut Node,
+    column: &dyn Fn(&str) -> Option<usize>,
+    accesses: &mut Vec<RowAccess>,
+    aggregates: &mut Vec<Aggregate>,
+) {
+    let variable = if let Some((function, column)) = aggregate(node, column) {
+        let variable = format!("#aggregate{}", aggregates.len());
+
+        aggregates.push(Aggregate {
+            variable: variable.clone(),
+            column,
+            function,
+            value: f64::NAN,
+        });
+
+        variable
+    } else if let Some((column, line)) = row_access(node, column) {
+        let variable = format!("#row{}", accesses.len());
+
+        accesses.push(RowAccess {
+            variable: variable.clone(),
+            column,
+            line,
+        });
+
+        variable
+    } else {
+        for child in node.children_mut() {
+            replace_reads(child, column, accesses, aggregates);
+        }
+
+        return;
+    };
+
+    *node.operator_mut() = Operator::VariableIdentifierRead {
+        identifier: variable,
+    };
+    node.children_mut().clear();
+}
+
+/// Name of the function of a call node and the column given as its only argument
+fn column_call<'a>(
+    node: &'a Node,
+    column: &dyn Fn(&str) -> Option<usize>,
+) -> Option<(&'a str, usize)> {
+    let Operator::FunctionIdentifier { identifier } = node.operator() else {
+        return None;
+    };
+
+    // The argument is a root node containing the value
+    let [variable] = node.children().first()?.children() else {
+        return None;
+    };
+    let Operator::VariableIdentifierRead { identifier: name } = variable.operator() else {
+        return None;
+    };
+
+    Some((identifier, column(name)?))
+}
+
+/// Function and column of a call node, if it is an aggregate
+fn aggregate(
+    node: &Node,
+    column: &dyn Fn(&str) -> Option<usize>,
+) -> Option<(AggregateFunction, usize)> {
+    let (name, column) = column_call(node, column)?;
+
+    Some((AggregateFunction::from_name(name)?, column))
+}
+
+/// Column and line read by a function call node, if it is an access to another line
+fn row_access(node: &Node, column: &dyn Fn(&str) -> Option<usize>) -> Option<(usize, LineIndex)> {
+    match column_call(node, column) {
+        Some(("prev", column)) => return Some((column, LineIndex::Relative(-1))),
+        Some(("next", column)) => return Some((column, LineIndex::Relative(1))),
+        _ => {}
+    }
+
+    let Operator::FunctionIdentifier { identifier } = node.operator() else {
+        return None;
+    };
+
+    Some((
+        column(identifier)?,
+        LineIndex::Expression(node.children().first()?.clone()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(expression: &str) -> ParsedExpression {
+        parse_row_accesses(expression, &|name| match name {
+            "x" => Some(1),
+            "y" => Some(2),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn neighbouring_lines_are_relative_accesses() {
+        let parsed = parse("next(x) - prev(y)");
+        let [next, prev] = parsed.accesses.as_slice() else {
+            panic!("expected two accesses");
+        };
+
+        assert_eq!((next.column, prev.column), (1, 2));
+        assert!(matches!(next.line, LineIndex::Relative(1)));
+        assert!(matches!(prev.line, LineIndex::Relative(-1)));
+        assert_eq!(prev.line(3), Some(2));
+        assert_eq!(next.line(3), Some(4));
+    }
+
+    #[test]
+    fn line_numbers_start_at_one() {
+        let parsed = parse("x(i - 2) + x(1) + x(0) + x(1.5)");
+        let lines = |line_n| {
+            parsed
+                .accesses
+                .iter()
+                .map(|access| access.line(line_n))
+                .collect::<Vec<_>>()
+        };
+
+        // The line 5 of the table is the index 4 of the grid
+        assert_eq!(lines(4), [Some(2), Some(0), None, None]);
+    }
+
+    #[test]
+    fn first_line_has_no_previous_line() {
+        let parsed = parse("prev(x) + x(i - 1)");
+
+        assert_eq!(parsed.accesses[0].line(0), None);
+        assert_eq!(parsed.accesses[1].line(0), None);
+        assert_eq!(parsed.accesses[1].line(1), Some(0));
+    }
+
+    #[test]
+    fn last_line_reads_past_the_grid() {
+        // The caller checks the line against the length of the grid, 3 lines here
+        let parsed = parse("next(x) + x(i + 1)");
+
+        assert_eq!(parsed.accesses[0].line(2), Some(3));
+        assert_eq!(parsed.accesses[1].line(2), Some(3));
+    }
+
+    #[test]
+    fn other_calls_are_left_alone() {
+        let parsed = parse("x + prev(3) + math::sqrt(x) + z(1)");
+
+        assert!(!parsed.reads_other_lines());
+    }
+
+    #[test]
+    fn aggregates_are_replaced_by_variables() {
+        let parsed = parse("x - mean(x) + stddev(y)");
+
+        assert!(parsed.accesses.is_empty());
+        assert_eq!(parsed.aggregates.len(), 2);
+        assert!(parsed.aggregates[0].function == AggregateFunction::Mean);
+        assert_eq!(parsed.aggregates[1].column, 2);
+        assert!(parsed
+            .tree
+            .iter_read_variable_identifiers()
+            .any(|name| name == parsed.aggregates[0].variable));
+    }
+}
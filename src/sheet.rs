@@ -0,0 +1,2088 @@
+use evalexpr::{
+    eval_number_with_context, Context as _, ContextWithMutableVariables, EvalexprError,
+    EvalexprResult, HashMapContext,
+};
+
+use crate::diagnostics::{error_message, BUILTIN_FUNCTIONS, COLUMN_FUNCTIONS};
+use crate::fit::{Fit, FIT_PARAMETERS};
+use crate::format::{format_measure, NumberFormat};
+use crate::functions::{functions_context, parse_library, UserFunction};
+use crate::number::DecimalSeparator;
+use crate::rows::{
+    line_number, parse_row_accesses, AggregateFunction, ParsedExpression, RowAccess, LINE_VARIABLE,
+};
+use crate::stats::Statistics;
+use crate::units::{expression_dimension, Dimension, Unit};
+
+const SAMPLE_COUNT: isize = 1;
+
+/// Value perturbed to propagate the uncertainties to the computed cells of a line
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Input {
+    Cell {
+        line: usize,
+        column: usize,
+    },
+    /// Cells of an aggregated column on the other lines, moved together by the same fraction of
+    /// their uncertainty
+    Aggregate {
+        column: usize,
+        function: AggregateFunction,
+    },
+    Constant(usize),
+    /// Index in `FIT_PARAMETERS`
+    FitParameter(usize),
+    /// Index in `externals`
+    External(usize),
+}
+
+/// Cell of the grid, as typed and as parsed or computed in the unit of its column
+#[derive(Clone)]
+pub struct Value {
+    pub raw_value: String,
+    pub raw_uncertainty: String,
+    pub value: f64,
+    pub uncertainty: f64,
+}
+
+impl Value {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            raw_value: String::new(),
+            raw_uncertainty: String::new(),
+            value: f64::NAN,
+            uncertainty: 0.,
+        }
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How the uncertainty of a measured cell is obtained when its Δ cell is left empty
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UncertaintyModel {
+    /// Every uncertainty is typed in its own cell, empty cells mean no uncertainty
+    PerCell,
+    /// The same uncertainty for every row
+    Constant,
+    /// An expression of `value`, e.g. `0.005*value + 0.01` for an instrument datasheet
+    Formula,
+    /// Type A (standard deviation of the column) combined with type B (formula of `value`)
+    TypeAB,
+}
+
+impl UncertaintyModel {
+    #[must_use]
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::PerCell => "per_cell",
+            Self::Constant => "constant",
+            Self::Formula => "formula",
+            Self::TypeAB => "type_ab",
+        }
+    }
+
+    #[must_use]
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "per_cell" => Some(Self::PerCell),
+            "constant" => Some(Self::Constant),
+            "formula" => Some(Self::Formula),
+            "type_ab" => Some(Self::TypeAB),
+            _ => None,
+        }
+    }
+}
+
+/// Column of the grid, measured if its expression is empty and computed otherwise
+#[derive(Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub expression: String,
+    pub unit: String,
+    /// Factor from the unit of the column to SI base units, 1 if the unit is invalid
+    pub unit_factor: f64,
+    pub precision: usize,
+    pub format: NumberFormat,
+    pub uncertainty_model: UncertaintyModel,
+    pub uncertainty_constant: String,
+    pub uncertainty_formula: String,
+    /// Hidden columns are still computed and plotted but not shown in the table
+    pub hidden: bool,
+    pub uncertainty_hidden: bool,
+    /// Calibration whose inverse gives the values, the expression is then generated from it
+    pub calibration: Option<Calibration>,
+}
+
+impl Column {
+    #[must_use]
+    pub const fn new(name: String) -> Self {
+        Self {
+            name,
+            expression: String::new(),
+            unit: String::new(),
+            unit_factor: 1.,
+            precision: 3,
+            format: NumberFormat::Fixed,
+            uncertainty_model: UncertaintyModel::PerCell,
+            uncertainty_constant: String::new(),
+            uncertainty_formula: String::new(),
+            hidden: false,
+            uncertainty_hidden: false,
+            calibration: None,
+        }
+    }
+
+    pub fn update_unit_factor(&mut self) {
+        self.unit_factor = Unit::parse(&self.unit).map_or(1., |unit| unit.factor);
+    }
+
+    /// Formats a value and its uncertainty with the display settings of the column
+    #[must_use]
+    pub fn format_measure(
+        &self,
+        value: f64,
+        uncertainty: f64,
+        separator: DecimalSeparator,
+    ) -> (String, String) {
+        let (value, uncertainty) = format_measure(value, uncertainty, self.format, self.precision);
+
+        (separator.localize(value), separator.localize(uncertainty))
+    }
+
+    /// Evaluates the instrument uncertainty formula for a given value
+    #[must_use]
+    // Setting a number in a new context can't fail
+    #[allow(clippy::missing_panics_doc)]
+    pub fn formula_uncertainty(&self, value: f64) -> f64 {
+        let mut context = HashMapContext::new();
+        context.set_value("value".to_owned(), value.into()).unwrap();
+
+        eval_number_with_context(&self.uncertainty_formula, &context).unwrap_or(f64::NAN)
+    }
+
+    /// Uncertainty given to a cell with an empty Δ, `type_a` is the standard deviation of the column
+    #[must_use]
+    pub fn default_uncertainty(&self, value: f64, type_a: f64, separator: DecimalSeparator) -> f64 {
+        if value.is_nan() {
+            return 0.;
+        }
+
+        match self.uncertainty_model {
+            UncertaintyModel::PerCell => 0.,
+            UncertaintyModel::Constant => separator
+                .parse(&self.uncertainty_constant)
+                .unwrap_or(f64::NAN),
+            UncertaintyModel::Formula => self.formula_uncertainty(value),
+            UncertaintyModel::TypeAB => {
+                let type_b = if self.uncertainty_formula.trim().is_empty() {
+                    0.
+                } else {
+                    self.formula_uncertainty(value)
+                };
+
+                type_a.hypot(type_b)
+            }
+        }
+    }
+}
+
+/// Fit `y = slope * x + height` of a calibration applied in reverse to raw readings, the column
+/// gives `x = (reading - height) / slope`
+///
+/// The uncertainties of the readings and of both parameters are propagated like in any
+/// expression, the height moving with the slope by their covariance
+#[derive(Clone, PartialEq, Eq)]
+pub struct Calibration {
+    /// Prefix of the fit parameters in expressions, `sheet.fit` for the fit of another sheet
+    pub fit: String,
+    /// Name of the column of the readings
+    pub reading: String,
+}
+
+impl Calibration {
+    #[must_use]
+    pub fn expression(&self) -> String {
+        format!(
+            "({} - {fit}.height) / {fit}.slope",
+            self.reading,
+            fit = self.fit
+        )
+    }
+}
+
+/// Named value usable in every expression, its uncertainty is propagated like the measured ones
+#[derive(Clone, PartialEq)]
+pub struct Constant {
+    pub name: String,
+    pub value: String,
+    pub uncertainty: String,
+    pub unit: String,
+    /// Value and uncertainty in SI base units, NaN if they are invalid
+    pub si_value: f64,
+    pub si_uncertainty: f64,
+}
+
+impl Constant {
+    #[must_use]
+    pub fn new(name: &str, value: &str, uncertainty: &str, unit: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            uncertainty: uncertainty.to_owned(),
+            unit: unit.to_owned(),
+            si_value: f64::NAN,
+            si_uncertainty: f64::NAN,
+        }
+    }
+
+    /// Physical constants which can be added from the constants panel
+    #[must_use]
+    pub fn presets() -> [Self; 3] {
+        [
+            Self::new("g", "9.81", "0.01", "m/s²"),
+            Self::new("c", "299792458", "", "m/s"),
+            Self::new("h", "6.62607015e-34", "", "J·s"),
+        ]
+    }
+
+    pub fn update_si_value(&mut self, separator: DecimalSeparator) {
+        let factor = Unit::parse(&self.unit).map_or(f64::NAN, |unit| unit.factor);
+        let uncertainty = if self.uncertainty.trim().is_empty() {
+            Some(0.)
+        } else {
+            separator.parse(&self.uncertainty)
+        };
+
+        self.si_value = separator.parse(&self.value).unwrap_or(f64::NAN) * factor;
+        self.si_uncertainty = uncertainty.unwrap_or(f64::NAN) * factor;
+    }
+}
+
+/// Value of another sheet of the project, read by the expressions as `sheet.name`
+#[derive(Clone)]
+pub struct External {
+    pub name: String,
+    /// Value and uncertainty in SI base units
+    pub value: f64,
+    pub uncertainty: f64,
+    pub dimension: Option<Dimension>,
+    /// Name of the value this one is correlated with and their covariance, the height of a fit
+    /// with its slope
+    pub covariance: Option<(String, f64)>,
+}
+
+/// `expression` where the identifiers starting with `old_prefix` start with `new_prefix` instead
+fn replace_prefix(expression: &str, old_prefix: &str, new_prefix: &str) -> String {
+    let identifier_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | ':');
+    let mut replaced = String::with_capacity(expression.len());
+    let mut rest = expression;
+    let mut previous = None;
+
+    while let Some(c) = rest.chars().next() {
+        // Only whole identifiers are replaced, not `bold.k` for the prefix `old.`
+        if !previous.is_some_and(identifier_char) && rest.starts_with(old_prefix) {
+            replaced.push_str(new_prefix);
+            rest = &rest[old_prefix.len()..];
+            previous = Some('.');
+        } else {
+            replaced.push(c);
+            rest = &rest[c.len_utf8()..];
+            previous = Some(c);
+        }
+    }
+
+    replaced
+}
+
+/// Measured and computed columns with their values, the constants and functions read by the
+/// expressions and the fit of the first column against the second one
+pub struct Sheet {
+    pub grid: Vec<Vec<Value>>,
+    /// Lines left out of the fit, missing entries at the end are not excluded
+    pub excluded_lines: Vec<bool>,
+    pub columns: Vec<Column>,
+    pub constants: Vec<Constant>,
+    /// User functions, one definition per line
+    pub function_library: String,
+    /// Functions parsed from the library and the invalid lines
+    pub user_functions: Vec<UserFunction>,
+    pub function_errors: Vec<String>,
+    /// Context where the user functions are defined, every expression context starts from it
+    function_context: HashMapContext,
+    pub linear_regression: bool,
+    pub decimal_separator: DecimalSeparator,
+    /// Prefix of the fit parameters in expressions
+    pub fit_name: String,
+    /// Columns of the y and x values of the fit, the first two ones unless they are chosen
+    pub fit_columns: [usize; 2],
+    /// Fit of the y column against the x column, updated with the computed values
+    pub fit: Fit,
+    /// Constants and fit parameters of the other sheets of the project
+    pub externals: Vec<External>,
+}
+
+impl Default for Sheet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sheet {
+    /// Sheet without columns, fitted by a line through the origin
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            grid: Vec::new(),
+            excluded_lines: Vec::new(),
+            columns: Vec::new(),
+            constants: Vec::new(),
+            function_library: String::new(),
+            user_functions: Vec::new(),
+            function_errors: Vec::new(),
+            function_context: HashMapContext::new(),
+            linear_regression: true,
+            decimal_separator: DecimalSeparator::Auto,
+            fit_name: "fit".to_owned(),
+            fit_columns: [0, 1],
+            fit: Fit::default(),
+            externals: Vec::new(),
+        }
+    }
+
+    /// Number of lines before the empty lines at the end of the grid
+    #[must_use]
+    pub fn filled_line_count(&self) -> usize {
+        self.grid
+            .iter()
+            .rposition(|line| {
+                line.iter()
+                    .any(|cell| !cell.raw_value.is_empty() || !cell.raw_uncertainty.is_empty())
+            })
+            .map_or(0, |last| last + 1)
+    }
+
+    pub fn add_column(&mut self, name: String) {
+        self.insert_column(self.columns.len(), name);
+    }
+
+    /// Inserts an empty measured column before the column `index`
+    pub fn insert_column(&mut self, index: usize, name: String) {
+        self.columns.insert(index, Column::new(name));
+
+        for line in &mut self.grid {
+            line.insert(index, Value::new());
+        }
+    }
+
+    pub fn remove_column(&mut self, index: usize) {
+        self.columns.remove(index);
+
+        for line in &mut self.grid {
+            line.remove(index);
+        }
+    }
+
+    /// Index of the copy of the column `index`, after the original so that its expression can
+    /// use the same columns, and after the two plotted columns
+    const fn copy_index(index: usize) -> usize {
+        if index + 1 > 2 {
+            index + 1
+        } else {
+            2
+        }
+    }
+
+    /// Why the column `index` can't be duplicated, a copy of the first column is placed after
+    /// the second one and can't read it anymore
+    #[must_use]
+    pub fn duplication_error(&self, index: usize) -> Option<String> {
+        if self.columns[index].expression.is_empty() {
+            return None;
+        }
+
+        let expression = self.parse_expression(index).ok()?;
+        let skipped = (index + 1)..Self::copy_index(index);
+
+        let read = skipped.clone().find(|column_n| {
+            let name = &self.columns[*column_n].name;
+
+            expression
+                .tree
+                .iter_read_variable_identifiers()
+                .any(|identifier| identifier == name)
+                || expression
+                    .accesses
+                    .iter()
+                    .any(|access| access.column == *column_n)
+                || expression
+                    .aggregates
+                    .iter()
+                    .any(|aggregate| aggregate.column == *column_n)
+        })?;
+
+        Some(format!(
+            "{} reads {}, a copy would be placed after {} and couldn't read it",
+            self.columns[index].name,
+            self.columns[read].name,
+            self.columns[skipped.end - 1].name,
+        ))
+    }
+
+    /// Copies a column with its values next to it and returns the index of the copy, fails with
+    /// the reason given by [`Sheet::duplication_error`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if the copy couldn't read the columns of the original
+    pub fn duplicate_column(&mut self, index: usize) -> Result<usize, String> {
+        if let Some(error) = self.duplication_error(index) {
+            return Err(error);
+        }
+
+        let copy_index = Self::copy_index(index);
+
+        let mut column = self.columns[index].clone();
+        column.name = self.unique_column_name(&column.name);
+        self.columns.insert(copy_index, column);
+
+        for line in &mut self.grid {
+            let value = line[index].clone();
+            line.insert(copy_index, value);
+        }
+
+        self.compute_and_parse_all();
+
+        Ok(copy_index)
+    }
+
+    /// `base` if no column has this name, otherwise `base` with the first free number
+    #[must_use]
+    pub fn unique_column_name(&self, base: &str) -> String {
+        let used = |name: &str| self.columns.iter().any(|column| column.name == name);
+
+        if !used(base) {
+            return base.to_owned();
+        }
+
+        let stem = base.trim_end_matches(|c: char| c.is_ascii_digit());
+
+        // One of these is free since there are fewer columns
+        (2..=self.columns.len() + 2)
+            .map(|n| format!("{stem}{n}"))
+            .find(|name| !used(name))
+            .unwrap_or_default()
+    }
+
+    /// First free single letter name, `i` is skipped since it is the line number
+    pub fn new_column_name(&self) -> String {
+        ('a'..='z')
+            .filter(|letter| *letter != 'i')
+            .map(String::from)
+            .find(|name| self.columns.iter().all(|column| column.name != *name))
+            .unwrap_or_else(|| self.unique_column_name("a"))
+    }
+
+    pub fn add_line(&mut self) {
+        self.grid.push(vec![Value::new(); self.columns.len()]);
+    }
+
+    /// Inserts a line before the line `line_n`, excluded from the fit if `excluded` is set
+    pub fn insert_line(&mut self, line_n: usize, line: Vec<Value>, excluded: bool) {
+        self.grid.insert(line_n, line);
+        self.excluded_lines.resize(self.grid.len() - 1, false);
+        self.excluded_lines.insert(line_n, excluded);
+    }
+
+    /// Removes the line `line_n`, returns its cells and whether it was excluded
+    pub fn remove_line(&mut self, line_n: usize) -> (Vec<Value>, bool) {
+        let excluded = self.line_excluded(line_n);
+
+        if line_n < self.excluded_lines.len() {
+            self.excluded_lines.remove(line_n);
+        }
+
+        (self.grid.remove(line_n), excluded)
+    }
+
+    pub fn ensure_empty_line(&mut self) {
+        let mut last_empty_line = 0;
+
+        'outer: for i in (0..self.grid.len()).rev() {
+            for x in 0..self.columns.len() {
+                if self.columns[x].expression.is_empty()
+                    && (!self.grid[i][x].raw_value.is_empty()
+                        || !self.grid[i][x].raw_uncertainty.is_empty())
+                {
+                    last_empty_line = i + 1;
+                    break 'outer;
+                }
+            }
+        }
+
+        if last_empty_line >= self.grid.len() {
+            self.add_line();
+        } else {
+            self.grid.drain((last_empty_line + 1)..self.grid.len());
+            self.excluded_lines.truncate(self.grid.len());
+        }
+    }
+
+    /// Parses the expression of a computed column, which can read the columns on its right and
+    /// the previous lines of itself on other lines
+    fn parse_expression(&self, column_n: usize) -> EvalexprResult<ParsedExpression> {
+        parse_row_accesses(&self.columns[column_n].expression, &|name| {
+            (column_n..self.columns.len()).find(|i| self.columns[*i].name == name)
+        })
+    }
+
+    /// Parsed expressions of the columns, `None` for measured columns and invalid expressions
+    fn parse_expressions(&self) -> Vec<Option<ParsedExpression>> {
+        (0..self.columns.len())
+            .map(|column_n| {
+                if self.columns[column_n].expression.is_empty() {
+                    None
+                } else {
+                    self.parse_expression(column_n).ok()
+                }
+            })
+            .collect()
+    }
+
+    /// Context where the expression of the column `column_n` is evaluated on the line `line_n`,
+    /// every value is in SI base units
+    fn line_context(
+        &self,
+        line_n: usize,
+        column_n: usize,
+        expression: &ParsedExpression,
+    ) -> HashMapContext {
+        let mut context = self.function_context.clone();
+
+        // Columns hide the constants with the same name
+        for constant in &self.constants {
+            context
+                .set_value(constant.name.clone(), constant.si_value.into())
+                .unwrap();
+        }
+
+        for (name, value, _) in self.fit_variables() {
+            context.set_value(name, value.into()).unwrap();
+        }
+
+        for external in &self.externals {
+            context
+                .set_value(external.name.clone(), external.value.into())
+                .unwrap();
+        }
+
+        for i in (column_n + 1)..self.columns.len() {
+            context
+                .set_value(
+                    self.columns[i].name.clone(),
+                    (self.grid[line_n][i].value * self.columns[i].unit_factor).into(),
+                )
+                .unwrap();
+        }
+
+        context
+            .set_value(LINE_VARIABLE.to_owned(), line_number(line_n).into())
+            .unwrap();
+
+        for access in &expression.accesses {
+            let value = self
+                .accessed_line(access, line_n, column_n)
+                .map_or(f64::NAN, |line| {
+                    self.grid[line][access.column].value * self.columns[access.column].unit_factor
+                });
+
+            context
+                .set_value(access.variable.clone(), value.into())
+                .unwrap();
+        }
+
+        for aggregate in &expression.aggregates {
+            // A column can't be aggregated in its own expression
+            let value = if aggregate.column == column_n {
+                f64::NAN
+            } else {
+                aggregate.value * self.columns[aggregate.column].unit_factor
+            };
+
+            context
+                .set_value(aggregate.variable.clone(), value.into())
+                .unwrap();
+        }
+
+        context
+    }
+
+    /// Line read by an access of the expression of `column_n` on the line `line_n`, a column can
+    /// only read itself on the lines already computed
+    fn accessed_line(&self, access: &RowAccess, line_n: usize, column_n: usize) -> Option<usize> {
+        access
+            .line(line_n)
+            .filter(|line| *line < self.grid.len() && (access.column != column_n || *line < line_n))
+    }
+
+    fn compute_line_value(&mut self, line_n: usize, expressions: &[Option<ParsedExpression>]) {
+        for column_n in (0..self.columns.len()).rev() {
+            if self.columns[column_n].expression.is_empty() {
+                continue;
+            }
+
+            let result = expressions[column_n]
+                .as_ref()
+                .map_or(f64::NAN, |expression| {
+                    expression
+                        .tree
+                        .eval_number_with_context(&self.line_context(line_n, column_n, expression))
+                        .unwrap_or(f64::NAN)
+                });
+
+            self.grid[line_n][column_n].value = result / self.columns[column_n].unit_factor;
+        }
+    }
+
+    /// Fit parameters as read by expressions, with their value and uncertainty in SI base units
+    pub fn fit_variables(&self) -> impl Iterator<Item = (String, f64, f64)> + '_ {
+        let [y, x] = self.fit_unit_factors();
+        let factors = [y / x, y];
+
+        FIT_PARAMETERS
+            .iter()
+            .enumerate()
+            .map(move |(parameter_n, parameter)| {
+                (
+                    format!("{}.{parameter}", self.fit_name.trim()),
+                    self.fit.values[parameter_n] * factors[parameter_n],
+                    self.fit.uncertainties[parameter_n] * factors[parameter_n],
+                )
+            })
+    }
+
+    /// Covariance of the fit slope and height in SI base units, like `fit_variables`
+    #[must_use]
+    pub fn fit_covariance(&self) -> f64 {
+        let [y, x] = self.fit_unit_factors();
+        self.fit.covariance * y.powi(2) / x
+    }
+
+    /// Unit factors of the y and x columns of the fit, a sheet without them has no fit, in SI
+    /// units anyway
+    fn fit_unit_factors(&self) -> [f64; 2] {
+        self.fit_columns
+            .map(|column_n| self.columns.get(column_n).map_or(1., |c| c.unit_factor))
+    }
+
+    /// Dimensions of the fit slope and height, `None` without fitted columns or where one has an
+    /// invalid unit
+    #[must_use]
+    pub fn fit_dimensions(&self) -> [Option<Dimension>; 2] {
+        if !self.has_fit_columns() {
+            return [None, None];
+        }
+
+        let y = self.column_dimension(self.fit_columns[0]).ok();
+        let x = self.column_dimension(self.fit_columns[1]).ok();
+        [y.zip(x).map(|(y, x)| y / x), y]
+    }
+
+    /// Whether the y and x columns of the fit exist
+    fn has_fit_columns(&self) -> bool {
+        self.fit_columns
+            .iter()
+            .all(|column_n| *column_n < self.columns.len())
+    }
+
+    /// Fits a calibration column can use, the fit of this sheet then the ones of the other
+    /// sheets
+    #[must_use]
+    pub fn calibration_sources(&self) -> Vec<String> {
+        let mut sources = vec![self.fit_name.trim().to_owned()];
+
+        sources.extend(self.externals.iter().filter_map(|external| {
+            let fit = external.name.strip_suffix(".slope")?;
+            let height = format!("{fit}.height");
+
+            self.externals
+                .iter()
+                .any(|other| other.name == height)
+                .then(|| fit.to_owned())
+        }));
+
+        sources
+    }
+
+    /// Makes the expressions and calibrations reading the values of the sheet `old_sheet` read
+    /// them from `new_sheet`, after the sheet is renamed
+    pub fn rename_sheet_references(&mut self, old_sheet: &str, new_sheet: &str) {
+        let old_prefix = format!("{old_sheet}.");
+        let new_prefix = format!("{new_sheet}.");
+
+        for column in &mut self.columns {
+            column.expression = replace_prefix(&column.expression, &old_prefix, &new_prefix);
+
+            if let Some(calibration) = &mut column.calibration {
+                calibration.fit = replace_prefix(&calibration.fit, &old_prefix, &new_prefix);
+            }
+        }
+    }
+
+    /// Constants and fit parameters as read by the expressions of the other sheets, prefixed by
+    /// `sheet_name.`
+    #[must_use]
+    pub fn exported_values(&self, sheet_name: &str) -> Vec<External> {
+        let mut values: Vec<External> = self
+            .constants
+            .iter()
+            .map(|constant| External {
+                name: format!("{sheet_name}.{}", constant.name),
+                value: constant.si_value,
+                uncertainty: constant.si_uncertainty,
+                dimension: Unit::parse(&constant.unit).ok().map(|unit| unit.dimension),
+                covariance: None,
+            })
+            .collect();
+
+        // The fit needs the y and x columns
+        if self.has_fit_columns() {
+            let dimensions = self.fit_dimensions();
+
+            let slope = format!(
+                "{sheet_name}.{}.{}",
+                self.fit_name.trim(),
+                FIT_PARAMETERS[0]
+            );
+            let covariances = [None, Some((slope, self.fit_covariance()))];
+
+            values.extend(self.fit_variables().zip(dimensions).zip(covariances).map(
+                |(((name, value, uncertainty), dimension), covariance)| External {
+                    name: format!("{sheet_name}.{name}"),
+                    value,
+                    uncertainty,
+                    dimension,
+                    covariance,
+                },
+            ));
+        }
+
+        values
+    }
+
+    /// Whether an expression reads the fit parameter `parameter_n`, or any parameter if `None`
+    fn reads_fit(
+        &self,
+        expressions: &[Option<ParsedExpression>],
+        parameter_n: Option<usize>,
+    ) -> bool {
+        let names: Vec<String> = self
+            .fit_variables()
+            .enumerate()
+            .filter(|(n, _)| parameter_n.is_none_or(|parameter_n| parameter_n == *n))
+            .map(|(_, (name, _, _))| name)
+            .collect();
+
+        expressions.iter().flatten().any(|expression| {
+            expression
+                .tree
+                .iter_read_variable_identifiers()
+                .any(|identifier| names.iter().any(|name| name == identifier))
+        })
+    }
+
+    /// Parses the function library and defines its functions for the expressions
+    fn update_functions(&mut self) {
+        (self.user_functions, self.function_errors) = parse_library(&self.function_library);
+        self.function_context = functions_context(&self.user_functions);
+    }
+
+    /// Dimension of the result of a call to one of the first `defined` user functions,
+    /// `Ok(None)` if its body calls a function whose dimension is unknown
+    fn user_function_dimension(
+        &self,
+        name: &str,
+        dimensions: &[Dimension],
+        defined: usize,
+    ) -> Option<Result<Option<Dimension>, String>> {
+        let (function_n, function) = self.user_functions[..defined]
+            .iter()
+            .enumerate()
+            .find(|(_, function)| function.name == name)?;
+
+        if !function.parameters.is_empty() && dimensions.len() != function.parameters.len() {
+            return Some(Err(format!(
+                "{name} takes {} arguments",
+                function.parameters.len()
+            )));
+        }
+
+        // The body can only call the functions defined before it
+        Some(expression_dimension(
+            &function.body,
+            &|variable| {
+                function
+                    .parameters
+                    .iter()
+                    .position(|parameter| parameter == variable)
+                    .map(|parameter_n| dimensions[parameter_n])
+            },
+            &|name, dimensions| self.user_function_dimension(name, dimensions, function_n),
+        ))
+    }
+
+    /// Fits the y column against the x column
+    pub fn update_fit(&mut self) {
+        if !self.has_fit_columns() {
+            self.fit = Fit::default();
+            return;
+        }
+
+        let [y, x] = self.fit_columns;
+
+        let points: Vec<(f64, f64)> = self
+            .grid
+            .iter()
+            .enumerate()
+            .map(|(line_n, line)| {
+                if self.line_excluded(line_n) {
+                    (f64::NAN, f64::NAN)
+                } else {
+                    (line[x].value, line[y].value)
+                }
+            })
+            .collect();
+
+        self.fit = Fit::new(&points, self.linear_regression);
+    }
+
+    /// Whether a line is left out of the fit, after an outlier test for example
+    #[must_use]
+    pub fn line_excluded(&self, line_n: usize) -> bool {
+        self.excluded_lines.get(line_n).copied().unwrap_or(false)
+    }
+
+    pub fn set_line_excluded(&mut self, line_n: usize, excluded: bool) {
+        if line_n >= self.excluded_lines.len() {
+            self.excluded_lines.resize(line_n + 1, false);
+        }
+
+        self.excluded_lines[line_n] = excluded;
+    }
+
+    /// Difference between the y value of each line and the fitted line, NaN for incomplete lines
+    #[must_use]
+    pub fn fit_residuals(&self) -> Vec<f64> {
+        let [y, x] = self.fit_columns;
+
+        self.grid
+            .iter()
+            .map(|line| line[y].value - self.fit.slope().mul_add(line[x].value, self.fit.height()))
+            .collect()
+    }
+
+    /// Dimension of the values of a column, computed from the expression for computed columns
+    ///
+    /// # Errors
+    ///
+    /// Fails if the unit is invalid or doesn't match the dimension of the expression
+    pub fn column_dimension(&self, column_n: usize) -> Result<Dimension, String> {
+        let column = &self.columns[column_n];
+        let unit = Unit::parse(&column.unit)?;
+
+        if column.expression.is_empty() {
+            return Ok(unit.dimension);
+        }
+
+        // The column itself can be read on other lines and has the dimension of its unit
+        let dimension = expression_dimension(
+            &column.expression,
+            &|name| {
+                if name == column.name {
+                    return Some(unit.dimension);
+                }
+
+                ((column_n + 1)..self.columns.len())
+                    .find(|i| self.columns[*i].name == name)
+                    .and_then(|i| self.column_dimension(i).ok())
+                    .or_else(|| {
+                        let constant = self.constants.iter().find(|c| c.name == name)?;
+                        Unit::parse(&constant.unit).ok().map(|unit| unit.dimension)
+                    })
+                    .or_else(|| {
+                        let external = self.externals.iter().find(|e| e.name == name)?;
+                        external.dimension
+                    })
+                    .or_else(|| {
+                        // The fitted columns can't depend on their own fit
+                        if self.fit_columns.contains(&column_n) {
+                            return None;
+                        }
+
+                        let parameter = name.strip_prefix(&format!("{}.", self.fit_name.trim()))?;
+                        let y = self.column_dimension(self.fit_columns[0]).ok()?;
+
+                        match parameter {
+                            "slope" => Some(y / self.column_dimension(self.fit_columns[1]).ok()?),
+                            "height" => Some(y),
+                            _ => None,
+                        }
+                    })
+            },
+            &|name, dimensions| {
+                self.user_function_dimension(name, dimensions, self.user_functions.len())
+            },
+        )?;
+
+        // Expressions calling a function of unknown dimension aren't checked
+        let Some(dimension) = dimension else {
+            return Ok(unit.dimension);
+        };
+
+        if column.unit.trim().is_empty() || dimension == unit.dimension {
+            Ok(dimension)
+        } else {
+            Err(format!(
+                "the expression gives {dimension}, which can't be converted to {}",
+                column.unit.trim()
+            ))
+        }
+    }
+
+    /// Unit shown next to the name of a column, computed columns without unit show the SI unit
+    /// of their result
+    #[must_use]
+    pub fn column_unit_label(&self, column_n: usize) -> String {
+        let column = &self.columns[column_n];
+
+        if !column.unit.trim().is_empty() {
+            column.unit.trim().to_owned()
+        } else if column.expression.is_empty() {
+            String::new()
+        } else {
+            match self.column_dimension(column_n) {
+                Ok(dimension) if !dimension.is_none() => dimension.to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    /// Name of a column followed by its unit
+    #[must_use]
+    pub fn column_title(&self, column_n: usize) -> String {
+        let unit = self.column_unit_label(column_n);
+
+        if unit.is_empty() {
+            self.columns[column_n].name.clone()
+        } else {
+            format!("{} ({unit})", self.columns[column_n].name)
+        }
+    }
+
+    /// Message of an error of the expression of the column `column_n`, reads of columns which
+    /// can't be seen from this column are explained
+    fn describe_error(&self, column_n: usize, error: &EvalexprError) -> String {
+        if let EvalexprError::VariableIdentifierNotFound(name) = error {
+            if *name == self.columns[column_n].name {
+                return format!(
+                    "a column can't read itself on the same line, prev({name}) reads the line \
+                     above"
+                );
+            }
+
+            if self.columns[..column_n]
+                .iter()
+                .any(|column| column.name == *name)
+            {
+                return format!("{name} is computed after this column and can't be read by it");
+            }
+        }
+
+        error_message(&self.columns[column_n].expression, error)
+    }
+
+    /// Why expressions can't read a column by its name, `None` if they can
+    #[must_use]
+    pub fn column_name_error(&self, column_n: usize) -> Option<String> {
+        (self.columns[column_n].name.trim() == LINE_VARIABLE).then(|| {
+            format!(
+                "{LINE_VARIABLE} is the line number in expressions, they can't read this column"
+            )
+        })
+    }
+
+    /// Why the expression of a computed column can't be evaluated, `None` if it is valid
+    #[must_use]
+    pub fn expression_error(&self, column_n: usize) -> Option<String> {
+        if self.columns[column_n].expression.is_empty() {
+            return None;
+        }
+
+        let error = match self.parse_expression(column_n) {
+            Err(error) => error,
+            Ok(expression) => expression
+                .tree
+                .eval_number_with_context(&self.line_context(0, column_n, &expression))
+                .err()?,
+        };
+
+        Some(self.describe_error(column_n, &error))
+    }
+
+    /// Why a cell has no value, or no uncertainty if `uncertainty` is set
+    #[must_use]
+    pub fn cell_error(&self, line_n: usize, column_n: usize, uncertainty: bool) -> String {
+        let column = &self.columns[column_n];
+        let value = &self.grid[line_n][column_n];
+
+        if column.expression.is_empty() {
+            return if !uncertainty {
+                format!("{} is not a number", value.raw_value.trim())
+            } else if !value.raw_uncertainty.trim().is_empty() {
+                format!("{} is not a number", value.raw_uncertainty.trim())
+            } else if column.uncertainty_model == UncertaintyModel::Constant {
+                "the uncertainty of the column is not a number".to_owned()
+            } else {
+                "the uncertainty formula of the column gives no value".to_owned()
+            };
+        }
+
+        if uncertainty && !value.value.is_nan() {
+            return "the uncertainty can't be propagated, the expression isn't defined around the \
+                    value or an input has no uncertainty"
+                .to_owned();
+        }
+
+        let mut expressions = self.parse_expressions();
+        self.update_aggregates(&mut expressions);
+
+        let Some(expression) = &expressions[column_n] else {
+            return self.expression_error(column_n).unwrap_or_default();
+        };
+
+        let context = self.line_context(line_n, column_n, expression);
+
+        if let Err(error) = expression.tree.eval_number_with_context(&context) {
+            return self.describe_error(column_n, &error);
+        }
+
+        let missing: Vec<String> = expression
+            .tree
+            .iter_read_variable_identifiers()
+            .filter(|name| {
+                matches!(context.get_value(name), Some(evalexpr::Value::Float(value)) if value.is_nan())
+            })
+            .map(|name| {
+                if let Some(access) = expression.accesses.iter().find(|a| a.variable == name) {
+                    let read = &self.columns[access.column].name;
+
+                    self.accessed_line(access, line_n, column_n).map_or_else(
+                        || format!("{read} is read outside of the table"),
+                        |line| format!("{read} has no value on line {}", line + 1),
+                    )
+                } else if let Some(aggregate) =
+                    expression.aggregates.iter().find(|a| a.variable == name)
+                {
+                    let call = format!(
+                        "{}({})",
+                        aggregate.function.name(),
+                        self.columns[aggregate.column].name
+                    );
+
+                    if aggregate.column == column_n {
+                        format!("{call} can't be computed in its own column")
+                    } else {
+                        format!("{call} has no value")
+                    }
+                } else {
+                    format!("{name} has no value on this line")
+                }
+            })
+            .collect();
+
+        if missing.is_empty() {
+            "the result is not a number, like the square root of a negative number".to_owned()
+        } else {
+            missing.join("\n")
+        }
+    }
+
+    /// Names proposed by the completion in the expression of the column `column_n`
+    #[must_use]
+    pub fn completion_names(&self, column_n: usize) -> Vec<String> {
+        let mut names: Vec<String> = self.columns[column_n..]
+            .iter()
+            .map(|column| column.name.clone())
+            .chain(self.constants.iter().map(|constant| constant.name.clone()))
+            .chain(self.fit_variables().map(|(name, _, _)| name))
+            .chain(self.externals.iter().map(|external| external.name.clone()))
+            .chain([LINE_VARIABLE.to_owned(), "pi".to_owned()])
+            .collect();
+
+        names.extend(
+            self.user_functions
+                .iter()
+                .map(|function| function.name.as_str())
+                .chain(COLUMN_FUNCTIONS)
+                .chain(BUILTIN_FUNCTIONS)
+                .map(|function| format!("{function}(")),
+        );
+
+        names
+    }
+
+    /// Value and uncertainty of an input of the uncertainty propagation
+    fn input_value(&self, input: Input) -> (f64, f64) {
+        match input {
+            Input::Cell { line, column } => (
+                self.grid[line][column].value,
+                self.grid[line][column].uncertainty,
+            ),
+            // The fraction of their uncertainty the cells are moved by
+            Input::Aggregate { .. } => (0., 1.),
+            Input::Constant(constant_n) => (
+                self.constants[constant_n].si_value,
+                self.constants[constant_n].si_uncertainty,
+            ),
+            Input::FitParameter(parameter_n) => (
+                self.fit.values[parameter_n],
+                self.fit.uncertainties[parameter_n],
+            ),
+            Input::External(external_n) => (
+                self.externals[external_n].value,
+                self.externals[external_n].uncertainty,
+            ),
+        }
+    }
+
+    /// Input which `input` is correlated with and their covariance: the height of a fit with its
+    /// slope
+    fn correlated_input(&self, input: Input) -> Option<(Input, f64)> {
+        match input {
+            Input::FitParameter(1) => Some((Input::FitParameter(0), self.fit.covariance)),
+            Input::External(external_n) => {
+                let (name, covariance) = self.externals[external_n].covariance.as_ref()?;
+                let partner = self
+                    .externals
+                    .iter()
+                    .position(|other| other.name == *name)?;
+                Some((Input::External(partner), *covariance))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sets an input to a sampled value, the cells of the aggregates are moved by
+    /// `compute_line_with_uncertainty` which knows the line
+    fn set_input_value(&mut self, input: Input, value: f64) {
+        match input {
+            Input::Cell { line, column } => self.grid[line][column].value = value,
+            Input::Aggregate { .. } => {}
+            Input::Constant(constant_n) => self.constants[constant_n].si_value = value,
+            Input::FitParameter(parameter_n) => self.fit.values[parameter_n] = value,
+            Input::External(external_n) => self.externals[external_n].value = value,
+        }
+    }
+
+    fn compute_line_with_uncertainty(
+        &mut self,
+        line_n: usize,
+        expressions: &mut [Option<ParsedExpression>],
+    ) {
+        self.compute_line_value(line_n, expressions);
+
+        // The measured cells of the line, the cells read on other lines and the aggregates are
+        // perturbed
+        let mut inputs: Vec<Input> = (0..self.columns.len())
+            .filter(|column_n| self.columns[*column_n].expression.is_empty())
+            .map(|column| Input::Cell {
+                line: line_n,
+                column,
+            })
+            .collect();
+
+        for expression in expressions.iter().flatten() {
+            for access in &expression.accesses {
+                if let Some(line) = access.line(line_n) {
+                    let input = Input::Cell {
+                        line,
+                        column: access.column,
+                    };
+
+                    if line != line_n && line < self.grid.len() && !inputs.contains(&input) {
+                        inputs.push(input);
+                    }
+                }
+            }
+
+            for aggregate in &expression.aggregates {
+                let input = Input::Aggregate {
+                    column: aggregate.column,
+                    function: aggregate.function,
+                };
+
+                if !inputs.contains(&input) {
+                    inputs.push(input);
+                }
+            }
+        }
+
+        // Only the constants read by an expression change the results
+        let read = |variable: &str| {
+            expressions.iter().flatten().any(|expression| {
+                expression
+                    .tree
+                    .iter_read_variable_identifiers()
+                    .any(|name| name == variable)
+            })
+        };
+
+        for (constant_n, constant) in self.constants.iter().enumerate() {
+            if read(&constant.name) && constant.si_uncertainty != 0. {
+                inputs.push(Input::Constant(constant_n));
+            }
+        }
+
+        for (external_n, external) in self.externals.iter().enumerate() {
+            if read(&external.name) && external.uncertainty != 0. {
+                inputs.push(Input::External(external_n));
+            }
+        }
+
+        for parameter_n in 0..FIT_PARAMETERS.len() {
+            if self.reads_fit(expressions, Some(parameter_n)) {
+                inputs.push(Input::FitParameter(parameter_n));
+            }
+        }
+
+        if inputs.is_empty() {
+            return;
+        }
+
+        let outputs: Vec<usize> = (0..self.columns.len())
+            .filter(|column_n| !self.columns[*column_n].expression.is_empty())
+            .collect();
+
+        let mut references: Vec<(f64, f64)> = inputs
+            .iter()
+            .map(|input| self.input_value(*input))
+            .collect();
+
+        // A correlated input follows its partner by their covariance over the uncertainty of the
+        // partner, and is sampled on its own with the rest of its uncertainty, so a fitted line
+        // pivots around the mean point instead of the origin
+        let correlations: Vec<Option<(usize, f64)>> = inputs
+            .iter()
+            .map(|input| {
+                let (partner, covariance) = self.correlated_input(*input)?;
+                let partner_index = inputs.iter().position(|other| *other == partner)?;
+                let uncertainty = references[partner_index].1;
+                (uncertainty != 0.).then(|| (partner_index, covariance / uncertainty))
+            })
+            .collect();
+
+        for (index, correlation) in correlations.iter().enumerate() {
+            if let Some((_, shift)) = correlation {
+                references[index].1 = (references[index].1.powi(2) - shift.powi(2)).max(0.).sqrt();
+            }
+        }
+
+        // Cells moved with each aggregate, as (line, value, change for the whole uncertainty),
+        // the cells which are inputs themselves are left to them
+        let moved_cells: Vec<Vec<(usize, f64, f64)>> = inputs
+            .iter()
+            .map(|input| match *input {
+                Input::Aggregate { column, function } => {
+                    self.aggregated_cells(line_n, column, function, &inputs)
+                }
+                _ => Vec::new(),
+            })
+            .collect();
+
+        let aggregated = inputs
+            .iter()
+            .any(|input| matches!(input, Input::Aggregate { .. }));
+        // Aggregates of computed columns also read the cell of this line once it is computed
+        let computed_aggregated = inputs.iter().any(|input| {
+            matches!(input, Input::Aggregate { column, .. }
+                if !self.columns[*column].expression.is_empty())
+        });
+
+        let mut samplers = vec![-SAMPLE_COUNT; inputs.len()];
+
+        let mut maxs = vec![f64::NAN; outputs.len()];
+        let mut mins = vec![f64::NAN; outputs.len()];
+
+        'outer: loop {
+            self.move_aggregated_cells(&inputs, &moved_cells, &samplers);
+
+            for (index, input) in inputs.iter().enumerate() {
+                let (value, uncertainty) = references[index];
+                let mut sample = value + samplers[index] as f64 * uncertainty / SAMPLE_COUNT as f64;
+
+                if let Some((partner_index, shift)) = correlations[index] {
+                    sample += samplers[partner_index] as f64 * shift / SAMPLE_COUNT as f64;
+                }
+
+                self.set_input_value(*input, sample);
+            }
+
+            if aggregated {
+                self.update_aggregates(expressions);
+            }
+
+            self.compute_line_value(line_n, expressions);
+
+            if computed_aggregated {
+                self.update_aggregates(expressions);
+                self.compute_line_value(line_n, expressions);
+            }
+
+            for (index, &column_n) in outputs.iter().enumerate() {
+                maxs[index] = maxs[index].max(self.grid[line_n][column_n].value);
+                mins[index] = mins[index].min(self.grid[line_n][column_n].value);
+            }
+
+            for i in (0..samplers.len()).rev() {
+                if samplers[i] < SAMPLE_COUNT {
+                    samplers[i] += 1;
+                    break;
+                } else if i == 0 {
+                    break 'outer;
+                }
+                samplers[i] = -SAMPLE_COUNT;
+            }
+        }
+
+        for (index, input) in inputs.iter().enumerate() {
+            self.set_input_value(*input, references[index].0);
+        }
+
+        self.move_aggregated_cells(&inputs, &moved_cells, &vec![0; samplers.len()]);
+
+        if aggregated {
+            self.update_aggregates(expressions);
+        }
+
+        for (index, &column_n) in outputs.iter().enumerate() {
+            self.grid[line_n][column_n].uncertainty = (maxs[index] - mins[index]) / 2.;
+        }
+
+        self.compute_line_value(line_n, expressions);
+    }
+
+    /// Cells of the column `column` on the other lines than `line_n` which move the aggregate,
+    /// as (line, value, change for the whole uncertainty in the direction increasing the
+    /// aggregate), without the cells which are inputs of the propagation themselves
+    fn aggregated_cells(
+        &self,
+        line_n: usize,
+        column: usize,
+        function: AggregateFunction,
+        inputs: &[Input],
+    ) -> Vec<(usize, f64, f64)> {
+        let values: Vec<f64> = self.grid.iter().map(|line| line[column].value).collect();
+        let mean = AggregateFunction::Mean.compute(&values);
+
+        self.grid
+            .iter()
+            .enumerate()
+            .filter(|(line, cells)| {
+                *line != line_n
+                    && !cells[column].value.is_nan()
+                    && cells[column].uncertainty.is_finite()
+                    && cells[column].uncertainty != 0.
+                    && !inputs.contains(&Input::Cell {
+                        line: *line,
+                        column,
+                    })
+            })
+            .map(|(line, cells)| {
+                let cell = &cells[column];
+
+                (
+                    line,
+                    cell.value,
+                    function.direction(cell.value, mean) * cell.uncertainty,
+                )
+            })
+            .collect()
+    }
+
+    /// Moves the cells of each aggregate by the fraction of their uncertainty given by its
+    /// sampler, samplers at 0 put them back at their value
+    fn move_aggregated_cells(
+        &mut self,
+        inputs: &[Input],
+        moved_cells: &[Vec<(usize, f64, f64)>],
+        samplers: &[isize],
+    ) {
+        // Several aggregates of a column add up their moves
+        for (index, input) in inputs.iter().enumerate() {
+            if let Input::Aggregate { column, .. } = *input {
+                for &(line, value, _) in &moved_cells[index] {
+                    self.grid[line][column].value = value;
+                }
+            }
+        }
+
+        for (index, input) in inputs.iter().enumerate() {
+            if let Input::Aggregate { column, .. } = *input {
+                let fraction = samplers[index] as f64 / SAMPLE_COUNT as f64;
+
+                for &(line, _, change) in &moved_cells[index] {
+                    self.grid[line][column].value += fraction * change;
+                }
+            }
+        }
+    }
+
+    /// Computes the aggregates read by the expressions from the current values of the grid
+    fn update_aggregates(&self, expressions: &mut [Option<ParsedExpression>]) {
+        for aggregate in expressions
+            .iter_mut()
+            .flatten()
+            .flat_map(|expression| &mut expression.aggregates)
+        {
+            let values: Vec<f64> = self
+                .grid
+                .iter()
+                .map(|line| line[aggregate.column].value)
+                .collect();
+
+            aggregate.value = aggregate.function.compute(&values);
+        }
+    }
+
+    pub fn compute_all(&mut self) {
+        self.update_functions();
+
+        for constant in &mut self.constants {
+            constant.update_si_value(self.decimal_separator);
+        }
+
+        let mut expressions = self.parse_expressions();
+
+        // Values read on the next lines, aggregates of computed columns and the fit are only
+        // known after a first pass, the passes are repeated until they don't change
+        let passes = if expressions
+            .iter()
+            .flatten()
+            .any(ParsedExpression::reads_other_lines)
+            || self.reads_fit(&expressions, None)
+        {
+            self.columns.len() + 1
+        } else {
+            1
+        };
+
+        for _ in 0..passes {
+            let previous = self.computed_state();
+            self.update_aggregates(&mut expressions);
+            self.update_fit();
+
+            for line_n in 0..self.grid.len() {
+                self.compute_line_with_uncertainty(line_n, &mut expressions);
+            }
+
+            if self.computed_state() == previous {
+                break;
+            }
+        }
+
+        self.update_fit();
+    }
+
+    /// Bits of every value and uncertainty, to know when computations stop changing
+    fn computed_state(&self) -> Vec<u64> {
+        self.grid
+            .iter()
+            .flatten()
+            .flat_map(|cell| [cell.value.to_bits(), cell.uncertainty.to_bits()])
+            .collect()
+    }
+
+    /// Parses a number typed in a cell, invalid numbers are NaN
+    #[must_use]
+    pub fn parse_number(&self, text: &str) -> f64 {
+        self.decimal_separator.parse(text).unwrap_or(f64::NAN)
+    }
+
+    /// Formats a number with the decimal separator of the settings
+    #[must_use]
+    pub fn format_number(&self, number: f64) -> String {
+        self.decimal_separator.localize(number.to_string())
+    }
+
+    /// Sample standard deviation of the measured values of a column
+    fn column_standard_deviation(&self, column_n: usize) -> f64 {
+        let values: Vec<f64> = self
+            .grid
+            .iter()
+            .map(|line| line[column_n].value)
+            .filter(|value| !value.is_nan())
+            .collect();
+
+        if values.len() < 2 {
+            return 0.;
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+
+        (values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.))
+            .sqrt()
+    }
+
+    /// Statistics of the values of a column, in its unit
+    #[must_use]
+    pub fn column_statistics(&self, column_n: usize) -> Statistics {
+        let cells: Vec<(f64, f64)> = self
+            .grid
+            .iter()
+            .map(|line| (line[column_n].value, line[column_n].uncertainty))
+            .collect();
+
+        Statistics::new(&cells)
+    }
+
+    /// Sets the uncertainty of the cells of a column from their Δ cell or from the column model
+    pub fn parse_column_uncertainties(&mut self, column_n: usize) {
+        let type_a = if self.columns[column_n].uncertainty_model == UncertaintyModel::TypeAB {
+            self.column_standard_deviation(column_n)
+        } else {
+            0.
+        };
+
+        for line_n in 0..self.grid.len() {
+            let cell = &self.grid[line_n][column_n];
+
+            self.grid[line_n][column_n].uncertainty = if cell.raw_uncertainty.trim().is_empty() {
+                self.columns[column_n].default_uncertainty(
+                    cell.value,
+                    type_a,
+                    self.decimal_separator,
+                )
+            } else {
+                self.parse_number(&cell.raw_uncertainty)
+            };
+        }
+    }
+
+    pub fn compute_and_parse_all(&mut self) {
+        for line_n in 0..self.grid.len() {
+            for column_n in 0..self.columns.len() {
+                self.grid[line_n][column_n].value =
+                    self.parse_number(&self.grid[line_n][column_n].raw_value);
+            }
+        }
+
+        for column_n in 0..self.columns.len() {
+            if self.columns[column_n].expression.is_empty() {
+                self.parse_column_uncertainties(column_n);
+            }
+        }
+
+        self.compute_all();
+    }
+
+    /// Parses a measured cell after an edit and recomputes what depends on it
+    pub fn parse_cell(&mut self, line_n: usize, column_n: usize) {
+        self.grid[line_n][column_n].value =
+            self.parse_number(&self.grid[line_n][column_n].raw_value);
+
+        if self.columns[column_n].uncertainty_model == UncertaintyModel::TypeAB {
+            // The type A part depends on every value of the column
+            self.parse_column_uncertainties(column_n);
+            self.compute_all();
+        } else {
+            let cell = &self.grid[line_n][column_n];
+
+            self.grid[line_n][column_n].uncertainty = if cell.raw_uncertainty.trim().is_empty() {
+                self.columns[column_n].default_uncertainty(cell.value, 0., self.decimal_separator)
+            } else {
+                self.parse_number(&cell.raw_uncertainty)
+            };
+
+            let mut expressions = self.parse_expressions();
+
+            if expressions
+                .iter()
+                .flatten()
+                .any(ParsedExpression::reads_other_lines)
+                || self.reads_fit(&expressions, None)
+            {
+                // Other lines can depend on this one
+                self.compute_all();
+            } else {
+                self.compute_line_with_uncertainty(line_n, &mut expressions);
+                self.update_fit();
+            }
+        }
+    }
+
+    /// Text of a cell as shown in the table, used to copy cells
+    #[must_use]
+    pub fn cell_text(&self, line: usize, column: usize, uncertainty: bool) -> String {
+        let cell = &self.grid[line][column];
+
+        if self.columns[column].expression.is_empty() && !uncertainty {
+            cell.raw_value.clone()
+        } else if self.columns[column].expression.is_empty() && !cell.raw_uncertainty.is_empty() {
+            cell.raw_uncertainty.clone()
+        } else if cell.value.is_nan() {
+            String::new()
+        } else {
+            let (value, uncertainty_text) = self.columns[column].format_measure(
+                cell.value,
+                cell.uncertainty,
+                self.decimal_separator,
+            );
+
+            if uncertainty {
+                uncertainty_text
+            } else {
+                value
+            }
+        }
+    }
+
+    /// Overwrites the first values of a measured column, the grid grows to fit them
+    pub fn fill_column(&mut self, column_index: usize, values: &[f64]) {
+        while self.grid.len() < values.len() {
+            self.add_line();
+        }
+
+        for (line, value) in self.grid.iter_mut().zip(values) {
+            line[column_index].raw_value = self.decimal_separator.localize(format!("{value}"));
+        }
+
+        self.ensure_empty_line();
+        self.compute_and_parse_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sheet computing `z = expression` from the measured columns `x` and `y`, one line per
+    /// `(x, Δx, y, Δy)`
+    fn sheet(expression: &str, lines: &[(&str, &str, &str, &str)]) -> Sheet {
+        let mut sheet = Sheet::new();
+
+        for name in ["z", "x", "y"] {
+            sheet.add_column(name.to_owned());
+        }
+        sheet.columns[0].expression = expression.to_owned();
+
+        for (line_n, (x, dx, y, dy)) in lines.iter().enumerate() {
+            sheet.add_line();
+            let line = &mut sheet.grid[line_n];
+            (line[1].raw_value, line[1].raw_uncertainty) = ((*x).to_owned(), (*dx).to_owned());
+            (line[2].raw_value, line[2].raw_uncertainty) = ((*y).to_owned(), (*dy).to_owned());
+        }
+
+        sheet.compute_and_parse_all();
+        sheet
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-12 * expected.abs().max(1.),
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn sum_adds_uncertainties() {
+        let sheet = sheet("x + y", &[("2", "0.1", "3", "0.2")]);
+
+        assert_close(sheet.grid[0][0].value, 5.);
+        assert_close(sheet.grid[0][0].uncertainty, 0.3);
+    }
+
+    #[test]
+    fn product_uncertainty_is_half_the_range() {
+        let sheet = sheet("x * y", &[("2", "0.1", "3", "0.2")]);
+
+        assert_close(sheet.grid[0][0].value, 6.);
+        // ((x + Δx)(y + Δy) - (x - Δx)(y - Δy)) / 2 = x Δy + y Δx
+        assert_close(sheet.grid[0][0].uncertainty, 2. * 0.2 + 3. * 0.1);
+    }
+
+    #[test]
+    fn quotient_uncertainty_is_half_the_range() {
+        let sheet = sheet("x / y", &[("2", "0.1", "4", "0.2")]);
+
+        assert_close(sheet.grid[0][0].value, 0.5);
+        assert_close(sheet.grid[0][0].uncertainty, (2.1 / 3.8 - 1.9 / 4.2) / 2.);
+    }
+
+    #[test]
+    fn extremes_inside_the_interval_are_sampled() {
+        // x² is minimal at x = 0, in the middle of [-1, 1]
+        let sheet = sheet("x^2", &[("0", "1", "", "")]);
+
+        assert_close(sheet.grid[0][0].value, 0.);
+        assert_close(sheet.grid[0][0].uncertainty, 0.5);
+    }
+
+    #[test]
+    fn constant_uncertainty_is_propagated() {
+        let mut sheet = sheet("x * k", &[("2", "0", "", "")]);
+        sheet.constants.push(Constant::new("k", "10", "1", ""));
+        sheet.compute_all();
+
+        assert_close(sheet.grid[0][0].value, 20.);
+        assert_close(sheet.grid[0][0].uncertainty, 2.);
+    }
+
+    #[test]
+    fn exact_inputs_give_exact_results() {
+        let sheet = sheet("x * y", &[("2", "", "3", "")]);
+
+        assert_close(sheet.grid[0][0].value, 6.);
+        assert_close(sheet.grid[0][0].uncertainty, 0.);
+    }
+
+    #[test]
+    fn units_are_converted_to_si() {
+        let mut sheet = sheet("x + y", &[("150", "", "1", "")]);
+        sheet.columns[1].unit = "cm".to_owned();
+        sheet.columns[1].update_unit_factor();
+        sheet.columns[2].unit = "m".to_owned();
+        sheet.columns[2].update_unit_factor();
+        sheet.compute_all();
+
+        assert_close(sheet.grid[0][0].value, 2.5);
+    }
+
+    #[test]
+    fn a_column_named_like_the_line_number_is_reported() {
+        let mut sheet = sheet("x", &[("1", "", "", "")]);
+        assert!(sheet.column_name_error(1).is_none());
+
+        sheet.columns[1].name = "i".to_owned();
+        assert!(sheet.column_name_error(1).is_some());
+    }
+
+    #[test]
+    fn milliseconds_convert_to_seconds_only() {
+        let mut sheet = sheet("x", &[("150", "", "", "")]);
+        sheet.columns[1].unit = "ms".to_owned();
+        sheet.columns[1].update_unit_factor();
+        sheet.columns[0].unit = "s".to_owned();
+        sheet.columns[0].update_unit_factor();
+        sheet.compute_all();
+
+        assert_close(sheet.grid[0][0].value, 0.15);
+        assert!(sheet.column_dimension(0).is_ok());
+
+        sheet.columns[0].unit = "m·s".to_owned();
+        sheet.columns[0].update_unit_factor();
+        assert_eq!(
+            sheet.column_dimension(0),
+            Err("the expression gives s, which can't be converted to m·s".to_owned())
+        );
+    }
+
+    #[test]
+    fn numbers_are_parsed_with_the_decimal_separator() {
+        let mut sheet = sheet(
+            "x",
+            &[
+                ("1,5", "", "", ""),
+                ("1 234.5", "", "", ""),
+                ("abc", "", "", ""),
+            ],
+        );
+
+        assert_close(sheet.grid[0][1].value, 1.5);
+        assert_close(sheet.grid[1][1].value, 1234.5);
+        assert!(sheet.grid[2][1].value.is_nan());
+        assert!(sheet.grid[2][0].value.is_nan());
+
+        sheet.decimal_separator = DecimalSeparator::Point;
+        sheet.compute_and_parse_all();
+        assert!(sheet.grid[0][1].value.is_nan());
+    }
+
+    #[test]
+    fn empty_uncertainties_follow_the_column_model() {
+        let mut sheet = sheet("x", &[("1", "", "", ""), ("3", "0.5", "", "")]);
+        assert_close(sheet.grid[0][1].uncertainty, 0.);
+
+        sheet.columns[1].uncertainty_model = UncertaintyModel::Constant;
+        sheet.columns[1].uncertainty_constant = "0.2".to_owned();
+        sheet.compute_and_parse_all();
+        assert_close(sheet.grid[0][1].uncertainty, 0.2);
+        // A typed uncertainty overrides the model
+        assert_close(sheet.grid[1][1].uncertainty, 0.5);
+
+        sheet.columns[1].uncertainty_model = UncertaintyModel::Formula;
+        sheet.columns[1].uncertainty_formula = "value / 10".to_owned();
+        sheet.compute_and_parse_all();
+        assert_close(sheet.grid[0][1].uncertainty, 0.1);
+
+        // Type A is the standard deviation of the column, √2 for 1 and 3
+        sheet.columns[1].uncertainty_model = UncertaintyModel::TypeAB;
+        sheet.columns[1].uncertainty_formula = String::new();
+        sheet.compute_and_parse_all();
+        assert_close(sheet.grid[0][1].uncertainty, 2f64.sqrt());
+    }
+
+    #[test]
+    fn externals_propagate_their_uncertainty() {
+        let mut sheet = sheet("x * other.k", &[("3", "", "", "")]);
+        sheet.externals.push(External {
+            name: "other.k".to_owned(),
+            value: 2.,
+            uncertainty: 0.1,
+            dimension: None,
+            covariance: None,
+        });
+        sheet.compute_all();
+
+        assert_close(sheet.grid[0][0].value, 6.);
+        assert_close(sheet.grid[0][0].uncertainty, 0.3);
+    }
+
+    #[test]
+    fn calibration_is_applied_in_reverse() {
+        let mut sheet = sheet("", &[("5", "0.1", "", "")]);
+        for (name, value, uncertainty) in [("cal.fit.slope", 2., 0.1), ("cal.fit.height", 1., 0.05)]
+        {
+            sheet.externals.push(External {
+                name: name.to_owned(),
+                value,
+                uncertainty,
+                dimension: None,
+                covariance: None,
+            });
+        }
+        assert_eq!(sheet.calibration_sources(), ["fit", "cal.fit"]);
+
+        let calibration = Calibration {
+            fit: "cal.fit".to_owned(),
+            reading: "x".to_owned(),
+        };
+        sheet.columns[0].expression = calibration.expression();
+        sheet.columns[0].calibration = Some(calibration);
+        sheet.compute_all();
+
+        assert_close(sheet.grid[0][0].value, 2.);
+        // Extremes at (5.1 - 0.95) / 1.9 and (4.9 - 1.05) / 2.1
+        assert_close(sheet.grid[0][0].uncertainty, (4.15 / 1.9 - 3.85 / 2.1) / 2.);
+
+        sheet.rename_sheet_references("cal", "reference");
+        assert_eq!(
+            sheet.columns[0].expression,
+            "(x - reference.fit.height) / reference.fit.slope"
+        );
+    }
+
+    #[test]
+    fn renamed_sheets_are_read_under_their_new_name() {
+        let mut sheet = sheet(
+            "cal.k * x + cal.fit.slope + local.k + x",
+            &[("1", "", "", "")],
+        );
+        sheet.rename_sheet_references("cal", "reference");
+        assert_eq!(
+            sheet.columns[0].expression,
+            "reference.k * x + reference.fit.slope + local.k + x"
+        );
+
+        sheet.rename_sheet_references("al", "other");
+        assert_eq!(
+            sheet.columns[0].expression,
+            "reference.k * x + reference.fit.slope + local.k + x"
+        );
+    }
+
+    #[test]
+    fn calibration_keeps_the_fit_correlation() {
+        // Fit of points around (4, 9), the line pivots around this point
+        let (slope, slope_uncertainty) = (2., 0.1);
+        let (height, height_uncertainty) = (1., 0.5);
+        let covariance = -4. * slope_uncertainty * slope_uncertainty;
+
+        let mut sheet = sheet("", &[("9", "", "", "")]);
+        sheet.externals.push(External {
+            name: "cal.fit.slope".to_owned(),
+            value: slope,
+            uncertainty: slope_uncertainty,
+            dimension: None,
+            covariance: None,
+        });
+        sheet.externals.push(External {
+            name: "cal.fit.height".to_owned(),
+            value: height,
+            uncertainty: height_uncertainty,
+            dimension: None,
+            covariance: Some(("cal.fit.slope".to_owned(), covariance)),
+        });
+
+        let calibration = Calibration {
+            fit: "cal.fit".to_owned(),
+            reading: "x".to_owned(),
+        };
+        sheet.columns[0].expression = calibration.expression();
+        sheet.columns[0].calibration = Some(calibration);
+        sheet.compute_all();
+
+        let x = sheet.grid[0][0].value;
+        assert_close(x, 4.);
+
+        // Analytic uncertainty of an inverse calibration, the terms of the slope and the
+        // covariance cancel at the mean point and leave the 0.3 of the height there
+        let analytic = (x * x)
+            .mul_add(
+                slope_uncertainty.powi(2),
+                height_uncertainty.powi(2) + 2. * x * covariance,
+            )
+            .sqrt()
+            / slope;
+        assert_close(analytic, 0.15);
+
+        // The sampling divides by the lowest slope instead, extremes at 4 ± 0.3 / 1.9, without
+        // the covariance they would be near 4 ± 0.45
+        assert_close(
+            sheet.grid[0][0].uncertainty,
+            analytic * slope / (slope - slope_uncertainty),
+        );
+    }
+
+    #[test]
+    fn copies_keep_reading_the_same_columns() {
+        let mut sheet = sheet("2 * y", &[("1", "", "3", "")]);
+
+        // The copy is placed after the plotted columns, where it still sees y
+        assert_eq!(sheet.duplicate_column(0), Ok(2));
+        assert_close(sheet.grid[0][2].value, 6.);
+    }
+
+    #[test]
+    fn first_column_reading_the_second_is_not_duplicated() {
+        let mut sheet = sheet("2 * x", &[("1", "", "3", "")]);
+
+        assert!(sheet.duplication_error(0).is_some());
+        assert!(sheet.duplicate_column(0).is_err());
+        assert_eq!(sheet.columns.len(), 3);
+        // The measured second column can be copied after itself
+        assert_eq!(sheet.duplicate_column(1), Ok(2));
+    }
+
+    #[test]
+    fn deviation_from_the_mean_keeps_the_correlation() {
+        let sheet = sheet(
+            "x - mean(x)",
+            &[
+                ("1", "0.1", "", ""),
+                ("2", "0.1", "", ""),
+                ("3", "0.1", "", ""),
+            ],
+        );
+
+        // The cell of the line counts for a third of the mean: Δx (1 - 1/3) + 2 Δx / 3
+        for line in &sheet.grid[..3] {
+            assert_close(line[0].uncertainty, 0.4 / 3.);
+        }
+    }
+
+    #[test]
+    fn ratio_to_the_maximum() {
+        let sheet = sheet(
+            "y / max(y)",
+            &[
+                ("", "", "1", "0.1"),
+                ("", "", "2", "0.1"),
+                ("", "", "4", "0.1"),
+            ],
+        );
+
+        assert_close(sheet.grid[0][0].uncertainty, (1.1 / 3.9 - 0.9 / 4.1) / 2.);
+        // The maximum moves with the cell of its line
+        assert_close(sheet.grid[2][0].value, 1.);
+        assert_close(sheet.grid[2][0].uncertainty, 0.);
+    }
+
+    #[test]
+    fn ensure_empty_line_adds_a_line_after_the_measures() {
+        let mut sheet = sheet("x", &[("1", "", "", "")]);
+        sheet.ensure_empty_line();
+
+        assert_eq!(sheet.grid.len(), 2);
+        assert_eq!(sheet.filled_line_count(), 1);
+    }
+
+    #[test]
+    fn ensure_empty_line_removes_the_extra_empty_lines() {
+        let mut sheet = sheet(
+            "x",
+            &[("1", "", "", ""), ("", "", "", ""), ("", "", "", "")],
+        );
+        sheet.ensure_empty_line();
+
+        assert_eq!(sheet.grid.len(), 2);
+    }
+
+    #[test]
+    fn ensure_empty_line_keeps_lines_with_only_an_uncertainty() {
+        let mut sheet = sheet("x", &[("1", "", "", ""), ("", "", "", "0.1")]);
+        sheet.ensure_empty_line();
+
+        assert_eq!(sheet.grid.len(), 3);
+    }
+
+    #[test]
+    fn ensure_empty_line_ignores_computed_columns() {
+        let mut sheet = sheet("x", &[]);
+        sheet.add_line();
+        sheet.grid[0][0].raw_value = "1".to_owned();
+        sheet.ensure_empty_line();
+
+        assert_eq!(sheet.grid.len(), 1);
+    }
+
+    #[test]
+    fn excluded_lines_are_left_out_of_the_fit() {
+        // The second point is 10 above the line y = 2x
+        let mut sheet = sheet(
+            "2 * x + y",
+            &[("1", "", "0", ""), ("2", "", "10", ""), ("3", "", "0", "")],
+        );
+        sheet.set_line_excluded(1, true);
+        sheet.compute_all();
+
+        assert_close(sheet.fit.slope(), 2.);
+        assert_close(sheet.fit_residuals()[1], 10.);
+
+        sheet.set_line_excluded(1, false);
+        sheet.compute_all();
+        assert!(sheet.fit.slope() > 2.);
+    }
+
+    #[test]
+    fn exclusion_follows_its_line() {
+        let mut sheet = sheet("x", &[("1", "", "", ""), ("2", "", "", "")]);
+        sheet.set_line_excluded(1, true);
+
+        let (line, excluded) = sheet.remove_line(1);
+        sheet.insert_line(0, line, excluded);
+
+        assert!(sheet.line_excluded(0));
+        assert!(!sheet.line_excluded(1));
+        assert_eq!(sheet.grid[0][1].raw_value, "2");
+    }
+}
@@ -0,0 +1,267 @@
+/// Descriptive statistics of the values of a column, cells without value are ignored
+#[derive(Clone, Copy)]
+pub struct Statistics {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, with `n - 1`
+    pub standard_deviation: f64,
+    /// Standard deviation of the mean, `s / √n`
+    pub standard_error: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Mean weighted by `1 / Δ²` and its uncertainty `1 / √Σ(1 / Δ²)`, NaN if a cell has no
+    /// uncertainty
+    pub weighted_mean: f64,
+    pub weighted_uncertainty: f64,
+}
+
+impl Statistics {
+    /// Statistics of cells given as (value, uncertainty)
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(cells: &[(f64, f64)]) -> Self {
+        let cells: Vec<(f64, f64)> = cells
+            .iter()
+            .copied()
+            .filter(|(value, _)| !value.is_nan())
+            .collect();
+
+        let mut values: Vec<f64> = cells.iter().map(|(value, _)| *value).collect();
+        values.sort_by(f64::total_cmp);
+
+        let count = values.len();
+        let n = count as f64;
+        let mean = values.iter().sum::<f64>() / n;
+
+        let median = quantile(&values, 0.5);
+
+        let standard_deviation = if count < 2 {
+            f64::NAN
+        } else {
+            (values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.))
+                .sqrt()
+        };
+
+        let (weighted_mean, weighted_uncertainty) = if count > 0
+            && cells
+                .iter()
+                .all(|(_, uncertainty)| uncertainty.is_finite() && *uncertainty > 0.)
+        {
+            let weight_sum: f64 = cells
+                .iter()
+                .map(|(_, uncertainty)| uncertainty.powi(-2))
+                .sum();
+            let weighted_sum: f64 = cells
+                .iter()
+                .map(|(value, uncertainty)| value / uncertainty.powi(2))
+                .sum();
+
+            (weighted_sum / weight_sum, weight_sum.sqrt().recip())
+        } else {
+            (f64::NAN, f64::NAN)
+        };
+
+        Self {
+            count,
+            mean,
+            median,
+            standard_deviation,
+            standard_error: standard_deviation / n.sqrt(),
+            min: values.first().copied().unwrap_or(f64::NAN),
+            max: values.last().copied().unwrap_or(f64::NAN),
+            weighted_mean,
+            weighted_uncertainty,
+        }
+    }
+}
+
+/// Quantile of sorted values, interpolated between the two closest values
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn quantile(sorted: &[f64], probability: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let position = probability * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+/// Density of the normal distribution of mean `mean` and standard deviation `deviation`
+#[must_use]
+pub fn normal_density(x: f64, mean: f64, deviation: f64) -> f64 {
+    (-0.5 * ((x - mean) / deviation).powi(2)).exp()
+        / (deviation * (2. * std::f64::consts::PI).sqrt())
+}
+
+/// Most bins in a histogram, narrower bins are widened to `range / MAX_BIN_COUNT`
+const MAX_BIN_COUNT: f64 = 1000.;
+
+/// How the width of the bins of a histogram is chosen
+#[derive(Clone, Copy, PartialEq)]
+pub enum BinWidth {
+    /// `⌈log₂ n⌉ + 1` bins over the range of the values
+    Sturges,
+    /// `2 IQR / ∛n`, less sensitive to outliers
+    FreedmanDiaconis,
+    Fixed(f64),
+}
+
+impl BinWidth {
+    /// Width of the bins for sorted values, 1 if the values don't give one
+    #[allow(clippy::cast_precision_loss)]
+    fn width(self, sorted: &[f64]) -> f64 {
+        let n = sorted.len() as f64;
+        let range = sorted.last().unwrap_or(&0.) - sorted.first().unwrap_or(&0.);
+
+        let width = match self {
+            Self::Sturges => range / (n.log2().ceil() + 1.),
+            Self::FreedmanDiaconis => {
+                let interquartile_range = quantile(sorted, 0.75) - quantile(sorted, 0.25);
+
+                if interquartile_range > 0. {
+                    2. * interquartile_range / n.cbrt()
+                } else {
+                    Self::Sturges.width(sorted)
+                }
+            }
+            Self::Fixed(width) => width,
+        };
+
+        if !(width.is_finite() && width > 0.) {
+            1.
+        } else if range / width > MAX_BIN_COUNT {
+            range / MAX_BIN_COUNT
+        } else {
+            width
+        }
+    }
+}
+
+/// Number of values in consecutive bins of equal width, the first one starts at the smallest
+/// value
+pub struct Histogram {
+    pub start: f64,
+    pub width: f64,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Histogram of the values which aren't NaN
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(values: &[f64], bin_width: BinWidth) -> Self {
+        let mut values: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        values.sort_by(f64::total_cmp);
+
+        let width = bin_width.width(&values);
+        let start = values.first().copied().unwrap_or(0.);
+        let range = values.last().copied().unwrap_or(0.) - start;
+        // The largest value closes the last bin instead of opening a new one
+        let bin_count = ((range / width).ceil() as usize).max(1);
+        let mut counts = vec![0; bin_count];
+
+        for value in values {
+            counts[(((value - start) / width) as usize).min(bin_count - 1)] += 1;
+        }
+
+        Self {
+            start,
+            width,
+            counts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= 1e-12, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn repeated_measures() {
+        let statistics =
+            Statistics::new(&[(2., 0.1), (f64::NAN, 0.), (4., 0.1), (9., 0.1), (1., 0.1)]);
+
+        assert_eq!(statistics.count, 4);
+        assert_close(statistics.mean, 4.);
+        assert_close(statistics.median, 3.);
+        assert_close(statistics.standard_deviation, (38f64 / 3.).sqrt());
+        assert_close(statistics.standard_error, (38f64 / 3.).sqrt() / 2.);
+        assert_close(statistics.min, 1.);
+        assert_close(statistics.max, 9.);
+        // Equal uncertainties give the plain mean with Δ / √n
+        assert_close(statistics.weighted_mean, 4.);
+        assert_close(statistics.weighted_uncertainty, 0.05);
+    }
+
+    #[test]
+    fn precise_measures_weigh_more() {
+        let statistics = Statistics::new(&[(1., 1.), (2., 0.5)]);
+
+        assert_close(statistics.weighted_mean, (1. + 2. * 4.) / 5.);
+        assert_close(statistics.weighted_uncertainty, 5f64.sqrt().recip());
+    }
+
+    #[test]
+    fn weighted_mean_needs_every_uncertainty() {
+        let statistics = Statistics::new(&[(1., 0.1), (2., 0.)]);
+
+        assert!(statistics.weighted_mean.is_nan());
+        assert_close(statistics.median, 1.5);
+    }
+
+    #[test]
+    fn empty_column() {
+        let statistics = Statistics::new(&[(f64::NAN, 0.)]);
+
+        assert_eq!(statistics.count, 0);
+        assert!(statistics.mean.is_nan() && statistics.median.is_nan() && statistics.min.is_nan());
+    }
+
+    #[test]
+    fn quartiles_are_interpolated() {
+        let sorted = [1., 2., 3., 4.];
+
+        assert_close(quantile(&sorted, 0.25), 1.75);
+        assert_close(quantile(&sorted, 0.75), 3.25);
+    }
+
+    #[test]
+    fn histogram_counts_every_value() {
+        let histogram = Histogram::new(&[0., 0.5, 1., 1.5, 2., f64::NAN], BinWidth::Fixed(1.));
+
+        assert_close(histogram.start, 0.);
+        // The largest value is counted in the last bin
+        assert_eq!(histogram.counts, [2, 3]);
+    }
+
+    #[test]
+    fn sturges_rule() {
+        let values: Vec<f64> = (0..8).map(f64::from).collect();
+        let histogram = Histogram::new(&values, BinWidth::Sturges);
+
+        // 8 values give 4 bins over a range of 7
+        assert_close(histogram.width, 7. / 4.);
+        assert_eq!(histogram.counts, [2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn equal_values_fill_one_bin() {
+        let histogram = Histogram::new(&[3., 3., 3.], BinWidth::FreedmanDiaconis);
+
+        assert_eq!(histogram.counts, [3]);
+    }
+}
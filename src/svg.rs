@@ -0,0 +1,195 @@
+use std::fmt::Write;
+
+use crate::fit::Fit;
+use crate::sheet::Sheet;
+
+const WIDTH: f64 = 640.;
+const HEIGHT: f64 = 480.;
+/// Space left around the plotting area for the ticks and the labels
+const LEFT_MARGIN: f64 = 80.;
+const RIGHT_MARGIN: f64 = 20.;
+const TOP_MARGIN: f64 = 20.;
+const BOTTOM_MARGIN: f64 = 60.;
+/// Approximate number of ticks on each axis
+const TICK_COUNT: u8 = 6;
+
+/// Range of the values shown on an axis, extended by a twentieth on both sides
+fn axis_range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+
+    if min > max {
+        (0., 1.)
+    } else if min < max {
+        let margin = (max - min) / 20.;
+        (min - margin, max + margin)
+    } else {
+        (min - 1., max + 1.)
+    }
+}
+
+/// Ticks of an axis, spaced by 1, 2 or 5 times a power of ten
+fn ticks((min, max): (f64, f64)) -> Vec<f64> {
+    let rough_step = (max - min) / f64::from(TICK_COUNT);
+    let power = 10f64.powf(rough_step.log10().floor());
+    let step = [1., 2., 5., 10.]
+        .into_iter()
+        .map(|factor| factor * power)
+        .find(|step| *step >= rough_step)
+        .unwrap_or(10. * power);
+
+    let first = (min / step).ceil() * step;
+
+    // The step is at least the rough step so there can't be more ticks
+    (0..=TICK_COUNT)
+        .map(|tick_n| first + f64::from(tick_n) * step)
+        .take_while(|tick| *tick <= max)
+        // Avoids showing -0 and rounding noise like 0.30000000000000004
+        .map(|tick| format!("{:.12e}", tick + 0.).parse().unwrap_or(tick))
+        .collect()
+}
+
+/// Escapes the characters of a text which have a meaning in XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Plot of the column `y` against the column `x` with the uncertainties as error bars and the
+/// fitted line if there is one, as an SVG image
+#[must_use]
+pub fn plot(sheet: &Sheet, x: usize, y: usize, fit: Option<&Fit>) -> String {
+    let points: Vec<(f64, f64, f64, f64)> = sheet
+        .grid
+        .iter()
+        .map(|line| {
+            (
+                line[x].value,
+                line[y].value,
+                line[x].uncertainty,
+                line[y].uncertainty,
+            )
+        })
+        .filter(|(x, y, _, _)| x.is_finite() && y.is_finite())
+        .collect();
+
+    let uncertainty = |uncertainty: f64| {
+        if uncertainty.is_finite() {
+            uncertainty
+        } else {
+            0.
+        }
+    };
+
+    let x_range = axis_range(
+        points
+            .iter()
+            .flat_map(|(x, _, dx, _)| [x - uncertainty(*dx), x + uncertainty(*dx)]),
+    );
+    let y_range = axis_range(
+        points
+            .iter()
+            .flat_map(|(_, y, _, dy)| [y - uncertainty(*dy), y + uncertainty(*dy)]),
+    );
+
+    let plot_width = WIDTH - LEFT_MARGIN - RIGHT_MARGIN;
+    let plot_height = HEIGHT - TOP_MARGIN - BOTTOM_MARGIN;
+    let to_x = |x: f64| LEFT_MARGIN + (x - x_range.0) / (x_range.1 - x_range.0) * plot_width;
+    let to_y = |y: f64| TOP_MARGIN + (y_range.1 - y) / (y_range.1 - y_range.0) * plot_height;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n\
+         <rect x=\"{LEFT_MARGIN}\" y=\"{TOP_MARGIN}\" width=\"{plot_width}\" \
+         height=\"{plot_height}\" fill=\"none\" stroke=\"black\"/>\n"
+    );
+
+    for tick in ticks(x_range) {
+        let position = to_x(tick);
+        let bottom = TOP_MARGIN + plot_height;
+
+        writeln!(
+            svg,
+            "<line x1=\"{position}\" y1=\"{bottom}\" x2=\"{position}\" y2=\"{}\" \
+             stroke=\"black\"/>\n\
+             <text x=\"{position}\" y=\"{}\" text-anchor=\"middle\">{tick}</text>",
+            bottom + 5.,
+            bottom + 20.
+        )
+        .unwrap();
+    }
+
+    for tick in ticks(y_range) {
+        let position = to_y(tick);
+
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{position}\" x2=\"{LEFT_MARGIN}\" y2=\"{position}\" \
+             stroke=\"black\"/>\n\
+             <text x=\"{}\" y=\"{position}\" text-anchor=\"end\" \
+             dominant-baseline=\"middle\">{tick}</text>",
+            LEFT_MARGIN - 5.,
+            LEFT_MARGIN - 8.
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n\
+         <text transform=\"translate(15 {}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+        LEFT_MARGIN + plot_width / 2.,
+        HEIGHT - 15.,
+        escape(&sheet.column_title(x)),
+        TOP_MARGIN + plot_height / 2.,
+        escape(&sheet.column_title(y)),
+    )
+    .unwrap();
+
+    for (x, y, dx, dy) in points {
+        let (dx, dy) = (uncertainty(dx), uncertainty(dy));
+
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"steelblue\"/>\n\
+             <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"steelblue\"/>\n\
+             <circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"steelblue\"/>",
+            to_x(x - dx),
+            to_y(y),
+            to_x(x + dx),
+            to_y(y),
+            to_x(x),
+            to_y(y - dy),
+            to_x(x),
+            to_y(y + dy),
+            to_x(x),
+            to_y(y),
+        )
+        .unwrap();
+    }
+
+    if let Some(fit) = fit.filter(|fit| fit.slope().is_finite() && fit.height().is_finite()) {
+        let line = |x: f64| fit.slope().mul_add(x, fit.height());
+
+        writeln!(
+            svg,
+            "<clipPath id=\"area\"><rect x=\"{LEFT_MARGIN}\" y=\"{TOP_MARGIN}\" \
+             width=\"{plot_width}\" height=\"{plot_height}\"/></clipPath>\n\
+             <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"firebrick\" \
+             clip-path=\"url(#area)\"/>",
+            to_x(x_range.0),
+            to_y(line(x_range.0)),
+            to_x(x_range.1),
+            to_y(line(x_range.1)),
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,650 @@
+use evalexpr::{build_operator_tree, Node, Operator};
+
+/// Names of the SI base units, in the order of the exponents of a [`Dimension`]
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// Exponents of the SI base units (kg, m, s, A, K, mol, cd)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Dimension([i32; 7]);
+
+impl Dimension {
+    pub const NONE: Self = Self([0; 7]);
+
+    const fn base(index: usize) -> Self {
+        let mut exponents = [0; 7];
+        exponents[index] = 1;
+        Self(exponents)
+    }
+
+    const fn new(kg: i32, m: i32, s: i32, a: i32) -> Self {
+        Self([kg, m, s, a, 0, 0, 0])
+    }
+
+    #[must_use]
+    pub fn is_none(self) -> bool {
+        self == Self::NONE
+    }
+
+    fn mul(self, other: Self) -> Self {
+        let mut exponents = self.0;
+        for (exponent, other) in exponents.iter_mut().zip(other.0) {
+            *exponent += other;
+        }
+        Self(exponents)
+    }
+
+    fn powi(self, power: i32) -> Self {
+        Self(self.0.map(|exponent| exponent * power))
+    }
+
+    /// Root of the dimension, fails if an exponent is not a multiple of `root`
+    fn root(self, root: i32) -> Option<Self> {
+        if self.0.iter().all(|exponent| exponent % root == 0) {
+            Some(Self(self.0.map(|exponent| exponent / root)))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::ops::Div for Dimension {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        self.mul(other.powi(-1))
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_none() {
+            return write!(f, "1");
+        }
+
+        if let Some((symbol, _)) = DERIVED_UNITS
+            .iter()
+            .find(|(_, dimension)| dimension == self)
+        {
+            return write!(f, "{symbol}");
+        }
+
+        let mut first = true;
+        for (symbol, exponent) in BASE_UNITS.iter().zip(self.0) {
+            if exponent == 0 {
+                continue;
+            }
+
+            if !first {
+                write!(f, "·")?;
+            }
+            first = false;
+
+            write!(f, "{symbol}")?;
+
+            if exponent != 1 {
+                write!(f, "{}", superscript(exponent))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn superscript(number: i32) -> String {
+    number
+        .to_string()
+        .chars()
+        .map(|c| match c {
+            '-' => '⁻',
+            '0' => '⁰',
+            '1' => '¹',
+            '2' => '²',
+            '3' => '³',
+            '4' => '⁴',
+            '5' => '⁵',
+            '6' => '⁶',
+            '7' => '⁷',
+            '8' => '⁸',
+            _ => '⁹',
+        })
+        .collect()
+}
+
+/// A unit is a scale factor to the SI base units and a dimension
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Unit {
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+impl Unit {
+    pub const NONE: Self = Self {
+        factor: 1.,
+        dimension: Dimension::NONE,
+    };
+
+    const fn new(factor: f64, dimension: Dimension) -> Self {
+        Self { factor, dimension }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.factor * other.factor,
+            self.dimension.mul(other.dimension),
+        )
+    }
+
+    fn powi(self, power: i32) -> Self {
+        Self::new(self.factor.powi(power), self.dimension.powi(power))
+    }
+
+    /// Parses units like `m`, `kg·m/s²`, `mV`, `kΩ` or `J/(mol·K)`, an empty string is dimensionless
+    ///
+    /// # Errors
+    ///
+    /// Fails on unknown units and invalid exponents
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.split('/');
+
+        let mut unit = parse_product(parts.next().unwrap_or_default())?;
+
+        for denominator in parts {
+            unit = unit.mul(parse_product(denominator)?.powi(-1));
+        }
+
+        Ok(unit)
+    }
+}
+
+const MASS: Dimension = Dimension::base(0);
+const LENGTH: Dimension = Dimension::base(1);
+const TIME: Dimension = Dimension::base(2);
+const CURRENT: Dimension = Dimension::base(3);
+
+const NEWTON: Dimension = Dimension::new(1, 1, -2, 0);
+const JOULE: Dimension = Dimension::new(1, 2, -2, 0);
+const WATT: Dimension = Dimension::new(1, 2, -3, 0);
+const VOLT: Dimension = Dimension::new(1, 2, -3, -1);
+
+/// Derived units that are shown by name when a result has their dimension
+const DERIVED_UNITS: [(&str, Dimension); 11] = [
+    ("Hz", Dimension::new(0, 0, -1, 0)),
+    ("N", NEWTON),
+    ("Pa", Dimension::new(1, -1, -2, 0)),
+    ("J", JOULE),
+    ("W", WATT),
+    ("C", Dimension::new(0, 0, 1, 1)),
+    ("V", VOLT),
+    ("Ω", Dimension::new(1, 2, -3, -2)),
+    ("F", Dimension::new(-1, -2, 4, 2)),
+    ("T", Dimension::new(1, 0, -2, -1)),
+    ("H", Dimension::new(1, 2, -2, -2)),
+];
+
+/// Every unit that can be written in a column unit, SI prefixes can be added to them
+fn named_unit(symbol: &str) -> Option<Unit> {
+    if let Some((_, dimension)) = DERIVED_UNITS.iter().find(|(name, _)| *name == symbol) {
+        return Some(Unit::new(1., *dimension));
+    }
+
+    Some(match symbol {
+        "1" | "rad" | "sr" => Unit::NONE,
+        "g" => Unit::new(1e-3, MASS),
+        "t" => Unit::new(1e3, MASS),
+        "m" => Unit::new(1., LENGTH),
+        "s" => Unit::new(1., TIME),
+        "min" => Unit::new(60., TIME),
+        "h" => Unit::new(3600., TIME),
+        "A" => Unit::new(1., CURRENT),
+        "K" => Unit::new(1., Dimension::base(4)),
+        "mol" => Unit::new(1., Dimension::base(5)),
+        "cd" => Unit::new(1., Dimension::base(6)),
+        "ohm" => Unit::new(1., Dimension::new(1, 2, -3, -2)),
+        "S" => Unit::new(1., Dimension::new(-1, -2, 3, 2)),
+        "Wb" => Unit::new(1., Dimension::new(1, 2, -2, -1)),
+        "L" | "l" => Unit::new(1e-3, LENGTH.powi(3)),
+        "bar" => Unit::new(1e5, Dimension::new(1, -1, -2, 0)),
+        "eV" => Unit::new(1.602_176_634e-19, JOULE),
+        "°" | "deg" => Unit::new(std::f64::consts::PI / 180., Dimension::NONE),
+        "%" => Unit::new(1e-2, Dimension::NONE),
+        _ => return None,
+    })
+}
+
+fn prefix_factor(prefix: &str) -> Option<f64> {
+    Some(match prefix {
+        "E" => 1e18,
+        "P" => 1e15,
+        "T" => 1e12,
+        "G" => 1e9,
+        "M" => 1e6,
+        "k" => 1e3,
+        "h" => 1e2,
+        "da" => 1e1,
+        "d" => 1e-1,
+        "c" => 1e-2,
+        "m" => 1e-3,
+        "µ" | "μ" | "u" => 1e-6,
+        "n" => 1e-9,
+        "p" => 1e-12,
+        "f" => 1e-15,
+        "a" => 1e-18,
+        _ => return None,
+    })
+}
+
+/// Parses a unit symbol with an optional SI prefix, the unprefixed reading is tried first so
+/// that `min`, `cd` or `Pa` are not read as prefixed units
+fn parse_symbol(symbol: &str) -> Option<Unit> {
+    if let Some(unit) = named_unit(symbol) {
+        return Some(unit);
+    }
+
+    symbol.char_indices().skip(1).find_map(|(split, _)| {
+        let factor = prefix_factor(&symbol[..split])?;
+        let unit = named_unit(&symbol[split..])?;
+        Some(Unit::new(factor * unit.factor, unit.dimension))
+    })
+}
+
+/// Parses a unit symbol followed by an optional exponent like `s²`, `s^-2` or `s-2`
+fn parse_factor(factor: &str) -> Result<Unit, String> {
+    let superscripts: String = factor
+        .chars()
+        .filter_map(|c| match c {
+            '⁻' => Some('-'),
+            '⁰' => Some('0'),
+            '¹' => Some('1'),
+            '²' => Some('2'),
+            '³' => Some('3'),
+            '⁴' => Some('4'),
+            '⁵' => Some('5'),
+            '⁶' => Some('6'),
+            '⁷' => Some('7'),
+            '⁸' => Some('8'),
+            '⁹' => Some('9'),
+            _ => None,
+        })
+        .collect();
+
+    let (symbol, exponent) = if superscripts.is_empty() {
+        match factor.find(|c: char| c == '^' || c == '-' || c.is_ascii_digit()) {
+            Some(0) | None => (factor, "1"),
+            Some(split) => (&factor[..split], factor[split..].trim_start_matches('^')),
+        }
+    } else {
+        (
+            factor.trim_end_matches(|c: char| "⁻⁰¹²³⁴⁵⁶⁷⁸⁹".contains(c)),
+            superscripts.as_str(),
+        )
+    };
+
+    let exponent = exponent
+        .parse::<i32>()
+        .map_err(|_| format!("invalid exponent in \"{factor}\""))?;
+
+    parse_symbol(symbol)
+        .map(|unit| unit.powi(exponent))
+        .ok_or_else(|| format!("unknown unit \"{symbol}\""))
+}
+
+fn parse_product(product: &str) -> Result<Unit, String> {
+    product
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(|c: char| c == '·' || c == '*' || c == '.' || c.is_whitespace())
+        .filter(|factor| !factor.is_empty())
+        .try_fold(Unit::NONE, |unit, factor| {
+            Ok(unit.mul(parse_factor(factor)?))
+        })
+}
+
+/// Builtin functions that return a value with the same dimension as their arguments
+const SAME_DIMENSION_FUNCTIONS: [&str; 11] = [
+    "min",
+    "max",
+    "floor",
+    "round",
+    "ceil",
+    "math::hypot",
+    "prev",
+    "next",
+    "mean",
+    "sum",
+    "stddev",
+];
+
+/// Builtin functions of dimensionless values, like the exponential or the trigonometric ones
+const DIMENSIONLESS_FUNCTIONS: [&str; 18] = [
+    "math::exp",
+    "math::exp2",
+    "math::ln",
+    "math::log",
+    "math::log2",
+    "math::log10",
+    "math::sin",
+    "math::cos",
+    "math::tan",
+    "math::asin",
+    "math::acos",
+    "math::atan",
+    "math::sinh",
+    "math::cosh",
+    "math::tanh",
+    "math::asinh",
+    "math::acosh",
+    "math::atanh",
+];
+
+/// Dimension of the result of a user function from the dimensions of its arguments, `None` if
+/// there is no such function and `Ok(None)` if its dimension can't be checked
+pub type FunctionDimension<'a> =
+    dyn Fn(&str, &[Dimension]) -> Option<Result<Option<Dimension>, String>> + 'a;
+
+/// Why the dimension of an expression isn't known
+enum DimensionError {
+    Invalid(String),
+    /// A function whose dimension is unknown is called, the expression isn't checked
+    Unchecked,
+}
+
+impl From<String> for DimensionError {
+    fn from(error: String) -> Self {
+        Self::Invalid(error)
+    }
+}
+
+/// Finds the dimension of the result of an expression, `variable` gives the dimension of each
+/// variable (unknown variables are dimensionless), `None` if it calls a function whose
+/// dimension is unknown
+///
+/// # Errors
+///
+/// Fails if the expression can't be parsed or combines incompatible dimensions
+pub fn expression_dimension(
+    expression: &str,
+    variable: &dyn Fn(&str) -> Option<Dimension>,
+    function: &FunctionDimension,
+) -> Result<Option<Dimension>, String> {
+    let tree = build_operator_tree(expression).map_err(|error| error.to_string())?;
+
+    match node_dimension(&tree, variable, function) {
+        Ok(dimension) => Ok(Some(dimension)),
+        Err(DimensionError::Unchecked) => Ok(None),
+        Err(DimensionError::Invalid(error)) => Err(error),
+    }
+}
+
+fn same_dimension(dimensions: &[Dimension], operation: &str) -> Result<Dimension, String> {
+    let first = dimensions.first().copied().unwrap_or_default();
+
+    if let Some(other) = dimensions.iter().find(|dimension| **dimension != first) {
+        Err(format!("cannot {operation} {first} and {other}"))
+    } else {
+        Ok(first)
+    }
+}
+
+/// Evaluates a constant exponent, exponents containing variables can only apply to dimensionless values
+fn constant_exponent(node: &Node) -> Result<f64, String> {
+    node.eval_number()
+        .map_err(|_| "the exponent of a value with a unit must be a constant".to_owned())
+}
+
+fn power_dimension(base: Dimension, exponent: f64) -> Result<Dimension, String> {
+    if exponent.fract() == 0. {
+        #[allow(clippy::cast_possible_truncation)]
+        return Ok(base.powi(exponent as i32));
+    }
+
+    let root = (1. / exponent).round();
+
+    #[allow(clippy::cast_possible_truncation)]
+    base.root(root as i32)
+        .filter(|_| (root * exponent - 1.).abs() < 1e-9)
+        .ok_or_else(|| format!("cannot raise {base} to the power {exponent}"))
+}
+
+fn node_dimension(
+    node: &Node,
+    variable: &dyn Fn(&str) -> Option<Dimension>,
+    function: &FunctionDimension,
+) -> Result<Dimension, DimensionError> {
+    if let Operator::FunctionIdentifier { identifier } = node.operator() {
+        return match node.children().first() {
+            Some(argument) => function_dimension(identifier, argument, variable, function),
+            None => Ok(Dimension::NONE),
+        };
+    }
+
+    let children = node
+        .children()
+        .iter()
+        .map(|child| node_dimension(child, variable, function))
+        .collect::<Result<Vec<Dimension>, DimensionError>>()?;
+
+    Ok(match (node.operator(), children.as_slice()) {
+        (Operator::VariableIdentifierRead { identifier }, _) => {
+            variable(identifier).unwrap_or_default()
+        }
+        (Operator::RootNode | Operator::Neg, [child]) => *child,
+        (Operator::Add | Operator::Sub, _) => same_dimension(&children, "add")?,
+        (Operator::Mod, [left, right]) => {
+            if left != right {
+                return Err(DimensionError::Invalid(format!(
+                    "cannot take the remainder of {left} divided by {right}"
+                )));
+            }
+
+            *left
+        }
+        (Operator::Mul, [left, right]) => left.mul(*right),
+        (Operator::Div, [left, right]) => *left / *right,
+        (Operator::Exp, [base, exponent]) => {
+            if !exponent.is_none() {
+                return Err(DimensionError::Invalid(format!(
+                    "an exponent cannot have the unit {exponent}"
+                )));
+            }
+
+            if base.is_none() {
+                *base
+            } else {
+                power_dimension(*base, constant_exponent(&node.children()[1])?)?
+            }
+        }
+        // Comparisons with plain numbers are allowed so they aren't checked
+        _ => Dimension::NONE,
+    })
+}
+
+fn function_dimension(
+    identifier: &str,
+    argument: &Node,
+    variable: &dyn Fn(&str) -> Option<Dimension>,
+    function: &FunctionDimension,
+) -> Result<Dimension, DimensionError> {
+    // Arguments are a root node containing either a single value or a tuple
+    let arguments: Vec<&Node> = match argument.children().first() {
+        Some(tuple) if matches!(tuple.operator(), Operator::Tuple) => {
+            tuple.children().iter().collect()
+        }
+        _ => vec![argument],
+    };
+
+    let dimensions = arguments
+        .iter()
+        .map(|argument| node_dimension(argument, variable, function))
+        .collect::<Result<Vec<Dimension>, DimensionError>>()?;
+
+    // User functions hide the builtin ones
+    if let Some(dimension) = function(identifier, &dimensions) {
+        return dimension?.ok_or(DimensionError::Unchecked);
+    }
+
+    let dimensionless = || {
+        dimensions
+            .iter()
+            .find(|dimension| !dimension.is_none())
+            .map_or(Ok(Dimension::NONE), |dimension| {
+                Err(DimensionError::Invalid(format!(
+                    "{identifier} needs a value without unit, not {dimension}"
+                )))
+            })
+    };
+
+    Ok(match identifier {
+        "math::sqrt" => power_dimension(dimensions[0], 0.5)?,
+        "math::cbrt" => power_dimension(dimensions[0], 1. / 3.)?,
+        "math::pow" if dimensions.len() == 2 => {
+            if dimensions[0].is_none() {
+                Dimension::NONE
+            } else {
+                power_dimension(dimensions[0], constant_exponent(arguments[1])?)?
+            }
+        }
+        // The angle of the point (x, y), both coordinates have the same unit
+        "math::atan2" => {
+            same_dimension(&dimensions, "compare")?;
+            Dimension::NONE
+        }
+        "if" if dimensions.len() == 3 => same_dimension(&dimensions[1..], "choose between")?,
+        _ if SAME_DIMENSION_FUNCTIONS.contains(&identifier) => {
+            same_dimension(&dimensions, "compare")?
+        }
+        _ if DIMENSIONLESS_FUNCTIONS.contains(&identifier) => dimensionless()?,
+        // A column read on another line, the argument is the line number
+        _ if variable(identifier).is_some() => {
+            if let Some(dimension) = dimensions.iter().find(|dimension| !dimension.is_none()) {
+                return Err(DimensionError::Invalid(format!(
+                    "a line number cannot have the unit {dimension}"
+                )));
+            }
+
+            variable(identifier).unwrap_or_default()
+        }
+        _ => return Err(DimensionError::Unchecked),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_unit(text: &str, factor: f64, dimension: Dimension) {
+        let unit = Unit::parse(text).unwrap();
+        assert!(
+            (unit.factor - factor).abs() <= 1e-12 * factor,
+            "{text}: {} != {factor}",
+            unit.factor
+        );
+        assert_eq!(unit.dimension, dimension, "{text}");
+    }
+
+    /// Dimension of an expression where `t` is a time and `d` a length
+    fn dimension(expression: &str) -> Result<Option<Dimension>, String> {
+        expression_dimension(
+            expression,
+            &|name| match name {
+                "t" => Some(TIME),
+                "d" => Some(LENGTH),
+                _ => None,
+            },
+            &|_, _| None,
+        )
+    }
+
+    #[test]
+    fn prefixes_scale_units() {
+        assert_unit("mV", 1e-3, VOLT);
+        assert_unit("kΩ", 1e3, Dimension::new(1, 2, -3, -2));
+        assert_unit("µs", 1e-6, TIME);
+        assert_unit("km/h", 1e3 / 3600., LENGTH / TIME);
+    }
+
+    #[test]
+    fn milliseconds_are_not_metre_seconds() {
+        assert_unit("ms", 1e-3, TIME);
+        assert_unit("m·s", 1., LENGTH.mul(TIME));
+        assert_unit("m s", 1., LENGTH.mul(TIME));
+        // Units which start like a prefix are read whole
+        assert_unit("min", 60., TIME);
+        assert_unit("Pa", 1., Dimension::new(1, -1, -2, 0));
+    }
+
+    #[test]
+    fn compound_units() {
+        assert_unit("kg·m/s²", 1., NEWTON);
+        assert_unit("kg*m*s^-2", 1., NEWTON);
+        assert_unit(
+            "J/(mol·K)",
+            1.,
+            JOULE / Dimension::base(5).mul(Dimension::base(4)),
+        );
+        assert_unit("g/cm3", 1e3, MASS / LENGTH.powi(3));
+        assert_unit("", 1., Dimension::NONE);
+    }
+
+    #[test]
+    fn invalid_units_are_reported() {
+        assert_eq!(
+            Unit::parse("furlong"),
+            Err("unknown unit \"furlong\"".to_owned())
+        );
+        assert_eq!(
+            Unit::parse("m^x"),
+            Err("invalid exponent in \"m^x\"".to_owned())
+        );
+    }
+
+    #[test]
+    fn dimensions_are_shown_with_derived_units() {
+        assert_eq!(NEWTON.to_string(), "N");
+        assert_eq!((LENGTH / TIME.powi(2)).to_string(), "m·s⁻²");
+        assert_eq!(Dimension::NONE.to_string(), "1");
+    }
+
+    #[test]
+    fn dimensions_propagate_through_expressions() {
+        assert_eq!(dimension("d / t"), Ok(Some(LENGTH / TIME)));
+        assert_eq!(
+            dimension("(d / t)^2 / 2"),
+            Ok(Some((LENGTH / TIME).powi(2)))
+        );
+        assert_eq!(dimension("math::sqrt(d^2 + d^2)"), Ok(Some(LENGTH)));
+        assert_eq!(dimension("max(t, 2 * t)"), Ok(Some(TIME)));
+    }
+
+    #[test]
+    fn mismatched_dimensions_are_errors() {
+        assert_eq!(dimension("t + d"), Err("cannot add s and m".to_owned()));
+        assert_eq!(
+            dimension("t % d"),
+            Err("cannot take the remainder of s divided by m".to_owned())
+        );
+        assert_eq!(
+            dimension("math::exp(t)"),
+            Err("math::exp needs a value without unit, not s".to_owned())
+        );
+        assert_eq!(
+            dimension("2^t"),
+            Err("an exponent cannot have the unit s".to_owned())
+        );
+        assert_eq!(
+            dimension("math::sqrt(t)"),
+            Err("cannot raise s to the power 0.5".to_owned())
+        );
+    }
+
+    #[test]
+    fn unknown_functions_are_not_checked() {
+        assert_eq!(dimension("calibrated(t) + d"), Ok(None));
+        assert_eq!(
+            expression_dimension("twice(t)", &|_| Some(TIME), &|name, dimensions| {
+                (name == "twice").then(|| Ok(Some(dimensions[0])))
+            }),
+            Ok(Some(TIME))
+        );
+    }
+}